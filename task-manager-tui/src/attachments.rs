@@ -0,0 +1,76 @@
+//! Links shown in the Attachments pane: URLs and files attached to a task,
+//! plus URLs written in its description.
+
+use crate::config;
+use crate::db::{Attachment, Task};
+
+/// One entry of the Attachments pane
+pub struct Link {
+    pub target: String,
+    /// None for a URL found in the description, which can't be removed here
+    pub attachment_id: Option<i32>,
+}
+
+pub fn is_url(target: &str) -> bool {
+    ["http://", "https://", "mailto:", "file://"]
+        .iter()
+        .any(|scheme| target.starts_with(scheme))
+}
+
+/// http(s) URLs in free text, without surrounding brackets or trailing
+/// punctuation
+pub fn detect_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let start = match word.find("https://").or_else(|| word.find("http://")) {
+            Some(start) => start,
+            None => continue,
+        };
+        let url = word[start..].trim_end_matches(|c: char| ").,;:!?'\"]>".contains(c));
+        if url.len() > "https://".len() && !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+/// Stored attachments first, then description URLs that aren't attached
+pub fn links(task: &Task, stored: &[Attachment]) -> Vec<Link> {
+    let mut links: Vec<Link> = stored
+        .iter()
+        .map(|attachment| Link {
+            target: attachment.target.clone(),
+            attachment_id: Some(attachment.id),
+        })
+        .collect();
+    for url in detect_urls(&task.description) {
+        if !links.iter().any(|link| link.target == url) {
+            links.push(Link {
+                target: url,
+                attachment_id: None,
+            });
+        }
+    }
+    links
+}
+
+/// Check what was typed into the attach prompt: a URL is kept as is, a path
+/// must exist and is stored absolute
+pub fn resolve(input: &str) -> Result<String, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("enter a URL or a file path".to_string());
+    }
+    if is_url(input) {
+        return Ok(input.to_string());
+    }
+    let path = config::expand_home(input);
+    path.canonicalize()
+        .map(|path| path.display().to_string())
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Open a URL or file with the system's default handler
+pub fn open(target: &str) -> Result<(), String> {
+    open::that_detached(target).map_err(|e| format!("can't open {}: {}", target, e))
+}
@@ -0,0 +1,172 @@
+//! Rotated copies of a task database, taken on startup and before schema
+//! migrations and bulk changes, and restoring one of them.
+
+use crate::config::{expand_home, BackupConfig};
+use crate::db::Database;
+use chrono::{Local, NaiveDateTime};
+use std::path::{Path, PathBuf};
+
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// A backup file, named `<database stem>.<id>.<reason>.db`
+pub struct Backup {
+    /// When it was taken, e.g. "20261018-142301", with a "-2" suffix for a
+    /// second one in the same second
+    pub id: String,
+    /// What triggered it: startup, migration, bulk, merge, purge or restore
+    pub reason: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+fn dir(config: &BackupConfig, db_path: &Path) -> PathBuf {
+    match &config.dir {
+        Some(dir) => expand_home(dir),
+        None => db_path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join("backups"),
+    }
+}
+
+fn prefix(db_path: &Path) -> String {
+    let stem = db_path.file_stem().unwrap_or_default().to_string_lossy();
+    format!("{}.", stem)
+}
+
+/// Backups of the database at `db_path`, oldest first
+pub fn list(config: &BackupConfig, db_path: &Path) -> std::io::Result<Vec<Backup>> {
+    let dir = dir(config, db_path);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let prefix = prefix(db_path);
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some((id, reason)) = name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(".db"))
+            .and_then(|rest| rest.split_once('.'))
+        else {
+            continue;
+        };
+        // Skips the backups of another database whose name starts the same
+        let time = id.get(..15).unwrap_or(id);
+        if NaiveDateTime::parse_from_str(time, ID_FORMAT).is_err() {
+            continue;
+        }
+        backups.push(Backup {
+            id: id.to_string(),
+            reason: reason.to_string(),
+            path: entry.path(),
+            size: entry.metadata()?.len(),
+        });
+    }
+    // By time, then "-2" before "-10"
+    backups.sort_by_key(|backup| {
+        (
+            backup.id.get(..15).map(String::from),
+            backup.id.len(),
+            backup.id.clone(),
+        )
+    });
+    Ok(backups)
+}
+
+/// Back up `db` and remove the oldest copies beyond `keep`. Returns the new
+/// backup's id, None when backups are turned off.
+pub fn take(
+    db: &Database,
+    config: &BackupConfig,
+    reason: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(id) = take_unrotated(db, config, reason)? else {
+        return Ok(None);
+    };
+    if let Some(db_path) = db.path() {
+        rotate(config, db_path)?;
+    }
+    Ok(Some(id))
+}
+
+fn take_unrotated(
+    db: &Database,
+    config: &BackupConfig,
+    reason: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let db_path = match db.path() {
+        Some(path) => path.to_path_buf(),
+        None => return Ok(None),
+    };
+    if config.keep == 0 {
+        return Ok(None);
+    }
+    let dir = dir(config, &db_path);
+    std::fs::create_dir_all(&dir)?;
+    let prefix = prefix(&db_path);
+    let time = Local::now().format(ID_FORMAT).to_string();
+    let existing = list(config, &db_path)?;
+    let mut id = time.clone();
+    for n in 2.. {
+        if !existing.iter().any(|backup| backup.id == id) {
+            break;
+        }
+        id = format!("{}-{}", time, n);
+    }
+    let path = dir.join(format!("{}{}.{}.db", prefix, id, reason));
+    db.backup_to(&path)
+        .map_err(|e| format!("backup to {} failed: {}", path.display(), e))?;
+    Ok(Some(id))
+}
+
+/// Remove the oldest backups beyond `keep`. Turning backups off keeps the
+/// existing ones.
+fn rotate(config: &BackupConfig, db_path: &Path) -> std::io::Result<()> {
+    if config.keep == 0 {
+        return Ok(());
+    }
+    let backups = list(config, db_path)?;
+    for old in &backups[..backups.len().saturating_sub(config.keep)] {
+        std::fs::remove_file(&old.path)?;
+    }
+    Ok(())
+}
+
+/// Replace the database with backup `id` once it passes an integrity check,
+/// backing up the current state first
+pub fn restore(
+    db: &mut Database,
+    config: &BackupConfig,
+    id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db_path = db.path().ok_or("not a database file")?.to_path_buf();
+    let backup = list(config, &db_path)?
+        .into_iter()
+        .find(|backup| backup.id == id)
+        .ok_or_else(|| format!("no backup '{}'; see `backup list`", id))?;
+    let problems = db
+        .check_backup(&backup.path)
+        .map_err(|e| format!("backup {} can't be read: {}", id, e))?;
+    if !problems.is_empty() {
+        return Err(format!("backup {} failed its check: {}", id, problems.join("; ")).into());
+    }
+    // Rotating only afterwards, as the backup being restored may be the
+    // oldest
+    if let Some(previous) = take_unrotated(db, config, "restore")? {
+        println!("Saved the current database as backup {}", previous);
+    }
+    db.restore_from(&backup.path)?;
+    println!("Restored backup {} ({})", id, backup.reason);
+    Ok(rotate(config, &db_path)?)
+}
+
+/// "12.5 KiB"
+pub fn format_size(size: u64) -> String {
+    match size {
+        0..=1023 => format!("{} B", size),
+        1024..=1048575 => format!("{:.1} KiB", size as f64 / 1024.0),
+        _ => format!("{:.1} MiB", size as f64 / 1048576.0),
+    }
+}
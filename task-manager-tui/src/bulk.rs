@@ -0,0 +1,137 @@
+//! Changes applied to every selected task at once, typed in the search bar
+//! like `status:completed pri:high cat:"Career Related" due:+3d parent:none est:2`.
+
+use crate::db::{self, Task};
+use crate::query::{self, DateValue};
+use crate::workflow::Workflow;
+use chrono::NaiveDate;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Status(String),
+    Priority(String),
+    Category(String),
+    /// None clears the due date
+    Due(Option<String>),
+    /// None moves the tasks to the top level
+    Parent(Option<i32>),
+    /// None clears the estimate
+    Estimate(Option<f64>),
+}
+
+impl Change {
+    pub fn apply(&self, task: &mut Task) {
+        match self {
+            Change::Status(status) => task.status = status.clone(),
+            Change::Priority(priority) => task.priority = priority.clone(),
+            Change::Category(category) => task.category = category.clone(),
+            Change::Due(due) => task.due_date = due.clone(),
+            Change::Parent(parent) => task.parent_task_id = *parent,
+            Change::Estimate(estimate) => task.estimate = *estimate,
+        }
+    }
+}
+
+/// Split on whitespace outside double quotes, dropping the quotes
+fn split_terms(input: &str) -> Result<Vec<String>, String> {
+    let mut terms = Vec::new();
+    let mut term = String::new();
+    let mut in_quotes = false;
+    for c in input.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !term.is_empty() {
+                    terms.push(std::mem::take(&mut term));
+                }
+            }
+            c => term.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("unterminated quote".to_string());
+    }
+    if !term.is_empty() {
+        terms.push(term);
+    }
+    Ok(terms)
+}
+
+/// The entry of `choices` that `value` names, ignoring case and spaces
+pub fn choose(value: &str, choices: &[&str]) -> Option<String> {
+    let normalize = |s: &str| s.to_lowercase().replace(' ', "");
+    choices
+        .iter()
+        .find(|choice| normalize(choice) == normalize(value))
+        .map(|choice| choice.to_string())
+}
+
+pub fn parse(input: &str, today: NaiveDate, workflow: &Workflow) -> Result<Vec<Change>, String> {
+    let mut changes = Vec::new();
+    for term in split_terms(input)? {
+        let (field, value) = term
+            .split_once(':')
+            .ok_or_else(|| format!("expected field:value, got '{}'", term))?;
+        if value.is_empty() {
+            return Err(format!("missing value after '{}'", field));
+        }
+        let change = match field.to_lowercase().as_str() {
+            "status" => Change::Status(
+                workflow
+                    .choose(value)
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown status '{}' ({})",
+                            value,
+                            workflow.names().join(", ")
+                        )
+                    })?
+                    .to_string(),
+            ),
+            "priority" | "pri" => {
+                Change::Priority(choose(value, &db::PRIORITIES).ok_or_else(|| {
+                    format!(
+                        "unknown priority '{}' ({})",
+                        value,
+                        db::PRIORITIES.join(", ")
+                    )
+                })?)
+            }
+            "category" | "cat" => Change::Category(
+                choose(value, &db::CATEGORIES).unwrap_or_else(|| value.to_string()),
+            ),
+            "due" => match query::parse_date(value) {
+                Some(DateValue::None) => Change::Due(None),
+                Some(date) => Change::Due(
+                    date.resolve(today)
+                        .map(|date| date.format("%Y-%m-%d").to_string()),
+                ),
+                None => return Err(format!("'{}' is not a date", value)),
+            },
+            "parent" if value.eq_ignore_ascii_case("none") => Change::Parent(None),
+            "parent" => {
+                Change::Parent(Some(value.parse().map_err(|_| {
+                    format!("parent must be a task id or none, got '{}'", value)
+                })?))
+            }
+            "estimate" | "est" if value.eq_ignore_ascii_case("none") => Change::Estimate(None),
+            "estimate" | "est" => match value.parse::<f64>() {
+                Ok(estimate) if estimate.is_finite() && estimate >= 0.0 => {
+                    Change::Estimate(Some(estimate))
+                }
+                _ => return Err(format!("'{}' is not an estimate", value)),
+            },
+            other => {
+                return Err(format!(
+                    "unknown field '{}' (use status, pri, cat, due, parent or est)",
+                    other
+                ))
+            }
+        };
+        changes.push(change);
+    }
+    if changes.is_empty() {
+        return Err("nothing to change".to_string());
+    }
+    Ok(changes)
+}
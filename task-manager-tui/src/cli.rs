@@ -0,0 +1,138 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Terminal task manager backed by SQLite
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Task database to open, overriding profiles
+    #[arg(long, env = "TASK_MANAGER_DB", global = true)]
+    pub db: Option<PathBuf>,
+
+    /// Profile from config.toml to open (e.g. work, personal)
+    #[arg(long, short, env = "TASK_MANAGER_PROFILE", global = true)]
+    pub profile: Option<String>,
+
+    /// Run a command instead of starting the TUI
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Merge the tasks of another tasks.db (e.g. a copy from another machine)
+    /// into this one
+    Merge {
+        /// The other database file, opened read-only; must have the current schema
+        other: PathBuf,
+    },
+
+    /// Import the tasks of a Taskwarrior `task export`, reporting the fields
+    /// that have no counterpart here
+    ImportTaskwarrior {
+        /// The export's JSON file, or - for stdin
+        file: PathBuf,
+    },
+
+    /// Serve a JSON HTTP API for the tasks on 127.0.0.1
+    Serve {
+        /// Port to listen on [default: server.port from config.toml, or 7878]
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Require `Authorization: Bearer <TOKEN>` on every request
+        #[arg(long, env = "TASK_MANAGER_TOKEN")]
+        token: Option<String>,
+    },
+
+    /// Encrypt the database in place with a new passphrase (needs a build
+    /// with `--features encryption`)
+    Encrypt,
+
+    /// Turn an encrypted database back into plain SQLite
+    Decrypt,
+
+    /// Change the passphrase of an encrypted database
+    Rekey,
+
+    /// List or restore the automatic backups of the database
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+
+    /// Add a task typed on one line, e.g. `Pay rent #home !high @tomorrow
+    /// 09:00 ^42`
+    Add {
+        /// The title plus #category or #tag, !priority, @due, HH:MM time and
+        /// ^parent id
+        #[arg(required = true)]
+        words: Vec<String>,
+
+        /// Only show how the line is read
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Link tasks to the commits of a git repository whose messages mention
+    /// `task #<id>` or `closes #<id>`
+    LinkCommits {
+        /// Repository to scan [default: git.repo from config.toml]
+        #[arg(long)]
+        repo: Option<PathBuf>,
+
+        /// Complete the tasks newly referred to with `closes #<id>`
+        #[arg(long)]
+        complete: bool,
+    },
+
+    /// Print a summary for shell prompts, e.g. "3 overdue · 5 today"
+    Prompt {
+        /// With {overdue}, {today} and {week} placeholders [default:
+        /// prompt.format from config.toml]
+        #[arg(long)]
+        format: Option<String>,
+    },
+
+    /// List the task templates or create tasks from one
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommand,
+    },
+
+    /// Permanently delete the tasks in the trash
+    Purge {
+        /// Delete the archived tasks as well
+        #[arg(long)]
+        archived: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BackupCommand {
+    /// List the backups, oldest first
+    List,
+
+    /// Replace the database with a backup once it passes an integrity check
+    Restore {
+        /// Backup id from `backup list`
+        id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TemplateCommand {
+    /// List the templates in the config directory's templates/
+    List,
+
+    /// Create a template's task tree
+    New {
+        /// Template name, or the start of one
+        name: String,
+
+        /// Date the template's due offsets count from, e.g. 2026-11-02 or
+        /// +7d [default: today]
+        #[arg(long, allow_hyphen_values = true)]
+        start: Option<String>,
+    },
+}
@@ -0,0 +1,328 @@
+use crate::workflow::Status;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One or more key chords bound to an action, e.g. `add = "ctrl+a"` or
+/// `quit = ["esc", "q"]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeySpec {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeySpec {
+    pub fn chords(&self) -> Vec<&str> {
+        match self {
+            KeySpec::One(chord) => vec![chord.as_str()],
+            KeySpec::Many(chords) => chords.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// User settings read from `config.toml`. Every section is optional.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Action name -> key chord(s), e.g. `[keys] add = "ctrl+n"`
+    pub keys: HashMap<String, KeySpec>,
+    /// Theme used until one is picked in the TUI
+    pub theme: Option<String>,
+    /// Profile name -> database path, e.g. `[profiles] work = "~/work/tasks.db"`
+    pub profiles: BTreeMap<String, String>,
+    /// Profile opened when neither `--db` nor `--profile` is given
+    pub default_profile: Option<String>,
+    pub server: ServerConfig,
+    pub urgency: UrgencyCoefficients,
+    /// Sort orders the sort key cycles through, e.g. `"due,priority,title"`
+    pub sort_orders: Vec<String>,
+    /// Completed tasks are archived this many days after completion; 0 turns
+    /// auto-archiving off
+    pub archive_after_days: u32,
+    pub planning: PlanningConfig,
+    pub backup: BackupConfig,
+    pub git: GitConfig,
+    pub prompt: PromptConfig,
+    /// `[[statuses]]` in workflow order; the built-in three when empty
+    pub statuses: Vec<Status>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keys: HashMap::new(),
+            theme: None,
+            profiles: BTreeMap::new(),
+            default_profile: None,
+            server: ServerConfig::default(),
+            urgency: UrgencyCoefficients::default(),
+            sort_orders: [
+                "-urgency",
+                "due,priority,title",
+                "priority,due,title",
+                "title",
+            ]
+            .map(String::from)
+            .to_vec(),
+            archive_after_days: 30,
+            planning: PlanningConfig::default(),
+            backup: BackupConfig::default(),
+            git: GitConfig::default(),
+            prompt: PromptConfig::default(),
+            statuses: Vec::new(),
+        }
+    }
+}
+
+/// `[server]` settings for `serve`; command line flags take precedence
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub port: u16,
+    pub token: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            port: 7878,
+            token: None,
+        }
+    }
+}
+
+/// `[urgency]` weights, in the spirit of Taskwarrior's urgency coefficients.
+/// Each factor is between 0 and 1 and multiplied by its coefficient.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UrgencyCoefficients {
+    pub priority_high: f64,
+    pub priority_medium: f64,
+    pub priority_low: f64,
+    /// Ramps up from two weeks before the due date to a week after it
+    pub due: f64,
+    /// Grows with age until `age_max_days`
+    pub age: f64,
+    pub age_max_days: f64,
+    /// Applied while a task has unfinished sub-tasks or depends on unfinished
+    /// tasks
+    pub blocked: f64,
+    /// Scaled by the share of sub-tasks completed
+    pub subtasks: f64,
+}
+
+impl Default for UrgencyCoefficients {
+    fn default() -> Self {
+        UrgencyCoefficients {
+            priority_high: 6.0,
+            priority_medium: 3.9,
+            priority_low: 1.8,
+            due: 12.0,
+            age: 2.0,
+            age_max_days: 365.0,
+            blocked: -5.0,
+            subtasks: 3.0,
+        }
+    }
+}
+
+/// `[planning]` settings for estimates and the planning view
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PlanningConfig {
+    /// Shown after estimates, e.g. "h" or "pts"
+    pub unit: String,
+    /// Estimate that fits in one work day
+    pub daily_capacity: f64,
+    /// Days that have capacity, e.g. `["mon", "tue", "wed", "thu", "fri"]`
+    pub work_days: Vec<String>,
+    /// Weigh sub-tasks by their estimates in a parent's progress instead of
+    /// counting them
+    pub progress_by_estimate: bool,
+}
+
+impl Default for PlanningConfig {
+    fn default() -> Self {
+        PlanningConfig {
+            unit: "h".to_string(),
+            daily_capacity: 6.0,
+            work_days: ["mon", "tue", "wed", "thu", "fri"]
+                .map(String::from)
+                .to_vec(),
+            progress_by_estimate: false,
+        }
+    }
+}
+
+/// `[backup]` settings for the automatic copies of the task database
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Copies kept per database, oldest removed first; 0 turns backups off
+    pub keep: usize,
+    /// Defaults to `backups` next to the database
+    pub dir: Option<String>,
+    /// Back up whenever the TUI or `serve` opens a database, not only before
+    /// migrations and bulk changes
+    pub on_startup: bool,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            keep: 10,
+            dir: None,
+            on_startup: true,
+        }
+    }
+}
+
+/// `[git]` settings for `link-commits`; command line flags take precedence
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    /// Local repository whose commits are scanned for task references
+    pub repo: Option<String>,
+    /// Complete the tasks a commit refers to with `closes #<id>`
+    pub complete_on_close: bool,
+}
+
+/// `[prompt]` settings for the shell prompt summary
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PromptConfig {
+    /// With `{overdue}`, `{today}` and `{week}` placeholders
+    pub format: String,
+    /// Splits `format` into parts, which are left out while their counts are
+    /// zero
+    pub separator: String,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        PromptConfig {
+            format: "{overdue} overdue · {today} today".to_string(),
+            separator: " · ".to_string(),
+        }
+    }
+}
+
+/// A named task database
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Config {
+    /// Directory holding `config.toml` (`~/.config/task_manager` on Linux)
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("task_manager"))
+    }
+
+    /// Load the config file, falling back to defaults when it doesn't exist
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = match Self::dir() {
+            Some(dir) => dir.join("config.toml"),
+            None => return Ok(Config::default()),
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let contents = fs::read_to_string(&path)?;
+        toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// `tasks.db` in the XDG data dir (`~/.local/share/task_manager` on Linux)
+    pub fn default_db_path() -> PathBuf {
+        dirs::data_dir()
+            .map(|dir| dir.join("task_manager"))
+            .unwrap_or_default()
+            .join("tasks.db")
+    }
+
+    /// The built-in "default" profile followed by the configured ones
+    pub fn profiles(&self) -> Vec<Profile> {
+        let mut profiles = vec![Profile {
+            name: "default".to_string(),
+            path: Self::default_db_path(),
+        }];
+        for (name, path) in &self.profiles {
+            let profile = Profile {
+                name: name.clone(),
+                path: expand_home(path),
+            };
+            match profiles.iter().position(|p| p.name == *name) {
+                Some(i) => profiles[i] = profile,
+                None => profiles.push(profile),
+            }
+        }
+        profiles
+    }
+
+    /// Pick the database to open: an explicit path wins, then a named
+    /// profile, then `default_profile`, then the XDG default.
+    pub fn resolve_profile(
+        &self,
+        db: Option<&Path>,
+        profile: Option<&str>,
+    ) -> Result<Profile, Box<dyn std::error::Error>> {
+        if let Some(path) = db {
+            let path = expand_home(&path.to_string_lossy());
+            let name = self
+                .profiles()
+                .into_iter()
+                .find(|p| p.path == path)
+                .map_or_else(|| path.display().to_string(), |p| p.name);
+            return Ok(Profile { name, path });
+        }
+        let name = profile
+            .or(self.default_profile.as_deref())
+            .unwrap_or("default");
+        self.profiles()
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown profile '{}' (add it under [profiles] in config.toml)",
+                    name
+                )
+                .into()
+            })
+    }
+}
+
+/// Expand a leading `~/` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Choices made inside the TUI that should survive a restart. Kept in
+/// `state.toml` next to config.toml so the user's config is never rewritten.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct State {
+    pub theme: Option<String>,
+}
+
+impl State {
+    /// Load the saved state; a missing or unreadable file just means no state
+    pub fn load() -> Self {
+        Config::dir()
+            .and_then(|dir| fs::read_to_string(dir.join("state.toml")).ok())
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = Config::dir().ok_or("no config directory")?;
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("state.toml"), toml::to_string(self)?)?;
+        Ok(())
+    }
+}
@@ -1,8 +1,18 @@
-use rusqlite::{params, Connection, Result};
+use crate::workflow::Workflow;
+use chrono::{NaiveDate, SecondsFormat, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::types::Value;
+use rusqlite::{
+    ffi, params, params_from_iter, Connection, Error, OpenFlags, OptionalExtension, Result, Row,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Task {
-    #[allow(dead_code)]
     pub id: i32,
     pub title: String,
     pub description: String,
@@ -12,35 +22,542 @@ pub struct Task {
     pub time: Option<String>,     // Format: "HH:MM"
     pub priority: String,
     pub status: String, // New field for task status
+    pub uuid: String,   // Stable across databases, generated on insert when empty
+    pub updated_at: String,
+    pub created_at: String,
+    pub archived_at: Option<String>,
+    /// Set while the task is in the trash
+    pub trashed_at: Option<String>,
+    /// Expected effort in hours or points (`[planning] unit`)
+    pub estimate: Option<f64>,
+    pub tags: Vec<String>,
+}
+
+/// Choices offered by the task dialog and bulk edits
+pub const CATEGORIES: [&str; 5] = ["Personal", "Work", "Development", "Home", "Career Related"];
+pub const PRIORITIES: [&str; 3] = ["High", "Medium", "Low"];
+
+/// Task fields whose last change time is tracked, so `merge` can pick the
+/// newer value field by field
+pub const TRACKED_FIELDS: [&str; 12] = [
+    "title",
+    "description",
+    "category",
+    "parent",
+    "due_date",
+    "time",
+    "priority",
+    "status",
+    "tags",
+    "archived",
+    "trashed",
+    "estimate",
+];
+
+/// Which tasks a listing covers. Archived and trashed tasks are hidden
+/// unless asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Active,
+    Archived,
+    Trash,
+    All,
+}
+
+impl Scope {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "active" => Some(Scope::Active),
+            "archive" | "archived" => Some(Scope::Archived),
+            "trash" | "trashed" => Some(Scope::Trash),
+            "all" => Some(Scope::All),
+            _ => None,
+        }
+    }
+
+    /// Condition on the tasks table
+    pub fn sql(self) -> &'static str {
+        match self {
+            Scope::Active => "(archived_at IS NULL AND trashed_at IS NULL)",
+            Scope::Archived => "(archived_at IS NOT NULL AND trashed_at IS NULL)",
+            Scope::Trash => "trashed_at IS NOT NULL",
+            Scope::All => "1 = 1",
+        }
+    }
+}
+
+impl Task {
+    /// Archived or in the trash
+    pub fn is_hidden(&self) -> bool {
+        self.archived_at.is_some() || self.trashed_at.is_some()
+    }
+
+    /// Past its due date and not yet done
+    pub fn is_overdue(&self, today: NaiveDate, workflow: &Workflow) -> bool {
+        !workflow.is_done(&self.status)
+            && self
+                .due_date
+                .as_deref()
+                .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok())
+                .is_some_and(|due| due < today)
+    }
+
+    /// Value of one of the `TRACKED_FIELDS`, as text
+    pub fn field(&self, field: &str) -> Option<String> {
+        match field {
+            "title" => Some(self.title.clone()),
+            "description" => Some(self.description.clone()),
+            "category" => Some(self.category.clone()),
+            "parent" => self.parent_task_id.map(|id| id.to_string()),
+            "due_date" => self.due_date.clone(),
+            "time" => self.time.clone(),
+            "priority" => Some(self.priority.clone()),
+            "status" => Some(self.status.clone()),
+            "tags" => Some(self.tags.join(" ")),
+            "archived" => self.archived_at.clone(),
+            "trashed" => self.trashed_at.clone(),
+            "estimate" => self.estimate.map(|estimate| estimate.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Set one of the `TRACKED_FIELDS` except "parent", which needs an id
+    pub fn set_field(&mut self, field: &str, value: Option<String>) {
+        match field {
+            "title" => self.title = value.unwrap_or_default(),
+            "description" => self.description = value.unwrap_or_default(),
+            "category" => self.category = value.unwrap_or_default(),
+            "due_date" => self.due_date = value,
+            "time" => self.time = value,
+            "priority" => self.priority = value.unwrap_or_default(),
+            "status" => self.status = value.unwrap_or_default(),
+            "tags" => self.tags = parse_tags(&value.unwrap_or_default()),
+            "archived" => self.archived_at = value,
+            "trashed" => self.trashed_at = value,
+            "estimate" => self.estimate = value.and_then(|v| v.parse().ok()),
+            _ => {}
+        }
+    }
+}
+
+/// Split user input like "urgent, #home errands" into distinct tags
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split(|c: char| c.is_whitespace() || c == ',') {
+        let tag = tag.trim_start_matches('#');
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// A task as exchanged by `merge`: the parent is referenced by uuid because
+/// row ids differ between databases
+#[derive(Debug, Clone)]
+pub struct SyncRecord {
+    pub task: Task,
+    pub parent_uuid: Option<String>,
+    pub field_times: HashMap<String, String>,
+}
+
+/// A named search with its sort and grouping, e.g. "Due this week"
+#[derive(Debug, Clone)]
+pub struct SavedView {
+    pub id: i32,
+    pub name: String,
+    pub query: String,
+    pub sort: String,
+    pub group_by: String,
+}
+
+/// A URL or local file path attached to a task
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub id: i32,
+    pub task_id: i32,
+    pub target: String,
+}
+
+/// A commit whose message refers to a task, found by `link-commits`
+#[derive(Debug, Clone)]
+pub struct CommitLink {
+    pub task_id: i32,
+    pub hash: String,
+    /// The branch `git log` reached it from, e.g. "main"
+    pub branch: Option<String>,
+    pub summary: String,
+    pub author: String,
+    pub committed_at: String,
+    /// Referred to as `closes #<id>` rather than `task #<id>`
+    pub closes: bool,
+}
+
+/// Open tasks by due date, for the `prompt` summary
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DueCounts {
+    pub overdue: usize,
+    pub today: usize,
+    /// Due from today through Sunday
+    pub week: usize,
+}
+
+/// Schema changes, applied in order. `PRAGMA user_version` stores how many
+/// have run, so append new entries and never edit old ones.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE IF NOT EXISTS tasks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        title TEXT NOT NULL,
+        description TEXT,
+        category TEXT,
+        parent_task_id INTEGER,
+        due_date TEXT,
+        time TEXT,
+        priority TEXT,
+        status TEXT NOT NULL DEFAULT 'To Do' -- Default status
+    );",
+    // 2: stable ids, change times and tombstones for `merge`
+    "ALTER TABLE tasks ADD COLUMN uuid TEXT;
+    ALTER TABLE tasks ADD COLUMN updated_at TEXT;
+    UPDATE tasks SET
+        uuid = lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || substr(hex(randomblob(2)), 2) || '-'
+            || substr('89ab', 1 + abs(random()) % 4, 1) || substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6))),
+        updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now');
+    CREATE UNIQUE INDEX tasks_uuid ON tasks (uuid);
+    CREATE TABLE task_field_times (
+        task_id INTEGER NOT NULL,
+        field TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY (task_id, field)
+    );
+    CREATE TABLE tombstones (
+        uuid TEXT PRIMARY KEY,
+        deleted_at TEXT NOT NULL
+    );
+    CREATE TABLE sync_meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // 3: free-form tags for the search bar's `tag:` filter
+    "CREATE TABLE task_tags (
+        task_id INTEGER NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (task_id, tag)
+    );",
+    // 4: saved views shown as tabs on the main screen
+    "CREATE TABLE saved_views (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE,
+        query TEXT NOT NULL,
+        sort TEXT NOT NULL DEFAULT '',
+        group_by TEXT NOT NULL DEFAULT ''
+    );
+    INSERT INTO saved_views (name, query, sort, group_by) VALUES
+        ('Due this week', 'due<=eow -status:completed', 'due,priority,title', ''),
+        ('High priority work', 'pri:high cat:work -status:completed', 'due,title', 'status'),
+        ('Overdue', 'due<today -status:completed', 'due,priority', 'category');",
+    // 5: creation time for the urgency score's age factor
+    "ALTER TABLE tasks ADD COLUMN created_at TEXT;
+    UPDATE tasks SET created_at = coalesce(
+        (SELECT min(updated_at) FROM task_field_times WHERE task_id = tasks.id),
+        updated_at
+    );",
+    // 6: archive and trash
+    "ALTER TABLE tasks ADD COLUMN archived_at TEXT;
+    ALTER TABLE tasks ADD COLUMN trashed_at TEXT;
+    INSERT INTO saved_views (name, query, sort, group_by) VALUES
+        ('Archive', 'in:archive', '-id', ''),
+        ('Trash', 'in:trash', '-id', '');",
+    // 7: links and files attached to tasks
+    "CREATE TABLE attachments (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        task_id INTEGER NOT NULL,
+        target TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        UNIQUE (task_id, target)
+    );",
+    // 8: effort estimates for workload planning
+    "ALTER TABLE tasks ADD COLUMN estimate REAL;",
+    // 9: tasks that must be done before another, e.g. Taskwarrior's depends
+    "CREATE TABLE task_dependencies (
+        task_id INTEGER NOT NULL,
+        depends_on INTEGER NOT NULL,
+        PRIMARY KEY (task_id, depends_on)
+    );",
+    // 10: git commits that refer to tasks
+    "CREATE TABLE commit_links (
+        task_id INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        branch TEXT,
+        summary TEXT NOT NULL,
+        author TEXT NOT NULL,
+        committed_at TEXT NOT NULL,
+        closes INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (task_id, hash)
+    );",
+    // 11: due date lookups for the `prompt` summary
+    "CREATE INDEX tasks_due_date ON tasks (due_date);",
+    // 12: default views hide every done status, not just "Completed"; views
+    // the user has edited are left alone
+    "UPDATE saved_views SET query = replace(query, '-status:completed', '-status:done')
+        WHERE (name, query) IN (
+            VALUES ('Due this week', 'due<=eow -status:completed'),
+                ('High priority work', 'pri:high cat:work -status:completed'),
+                ('Overdue', 'due<today -status:completed')
+        );",
+];
+
+const TASK_COLUMNS: &str = "id, title, description, category, parent_task_id, due_date, time, priority, status, uuid, updated_at, created_at,
+    archived_at, trashed_at, estimate, (SELECT group_concat(tag, ' ') FROM task_tags WHERE task_tags.task_id = tasks.id)";
+
+fn task_from_row(row: &Row) -> Result<Task> {
+    Ok(Task {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+        category: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+        parent_task_id: row.get(4)?,
+        due_date: row.get(5)?,
+        time: row.get(6)?,
+        priority: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+        status: row.get(8)?,
+        uuid: row.get(9)?,
+        updated_at: row.get::<_, Option<String>>(10)?.unwrap_or_default(),
+        created_at: row.get::<_, Option<String>>(11)?.unwrap_or_default(),
+        archived_at: row.get(12)?,
+        trashed_at: row.get(13)?,
+        estimate: row.get(14)?,
+        tags: parse_tags(&row.get::<_, Option<String>>(15)?.unwrap_or_default()),
+    })
+}
+
+/// Current UTC time in the format stored in `updated_at` columns
+pub fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
 }
 
 pub struct Database {
     connection: Connection,
+    /// SQLCipher passphrase, reused for backups
+    key: Option<String>,
+    /// Status changes made through `update_task` and `set_status` have to
+    /// follow it
+    workflow: Workflow,
 }
 
 impl Database {
-    pub fn new(db_file: &str) -> Result<Self> {
+    /// Open a database. Call `migrate` before using it.
+    pub fn new(db_file: impl AsRef<Path>) -> Result<Self> {
+        Self::setup(Connection::open(db_file)?, None)
+    }
+
+    /// Open a SQLCipher database. A wrong passphrase fails with
+    /// `ErrorCode::NotADatabase`.
+    #[cfg(feature = "encryption")]
+    pub fn open_encrypted(db_file: impl AsRef<Path>, passphrase: &str) -> Result<Self> {
         let connection = Connection::open(db_file)?;
-        connection.execute(
-            "CREATE TABLE IF NOT EXISTS tasks (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                title TEXT NOT NULL,
-                description TEXT,
-                category TEXT,
-                parent_task_id INTEGER,
-                due_date TEXT,
-                time TEXT,
-                priority TEXT,
-                status TEXT NOT NULL DEFAULT 'To Do' -- Default status
-            )",
-            [],
-        )?;
-        Ok(Database { connection })
-    }
-
-    pub fn add_task(&self, task: &Task) -> Result<()> {
-        self.connection.execute(
-            "INSERT INTO tasks (title, description, category, parent_task_id, due_date, time, priority, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        connection.pragma_update(None, "key", passphrase)?;
+        Self::setup(connection, Some(passphrase.to_string()))
+    }
+
+    /// Open a database without writing to it: no migrations and no change of
+    /// journal mode, so it is quick to open. Check `is_current` before
+    /// querying, as an older schema lacks columns the queries use.
+    pub fn open_read_only(db_file: impl AsRef<Path>, passphrase: Option<&str>) -> Result<Self> {
+        let connection = Connection::open_with_flags(db_file, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        if let Some(passphrase) = passphrase {
+            connection.pragma_update(None, "key", passphrase)?;
+            // Fails with `ErrorCode::NotADatabase` for a wrong passphrase
+            connection.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?;
+        }
+        connection.busy_timeout(Duration::from_millis(100))?;
+        Ok(Database {
+            connection,
+            key: passphrase.map(String::from),
+            workflow: Workflow::default(),
+        })
+    }
+
+    fn setup(connection: Connection, key: Option<String>) -> Result<Self> {
+        // WAL lets the TUI and `serve` use the same file at once
+        connection.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        connection.busy_timeout(Duration::from_secs(5))?;
+        Ok(Database {
+            connection,
+            key,
+            workflow: Workflow::default(),
+        })
+    }
+
+    pub fn set_workflow(&mut self, workflow: Workflow) {
+        self.workflow = workflow;
+    }
+
+    pub fn workflow(&self) -> &Workflow {
+        &self.workflow
+    }
+
+    /// The workflow's done statuses as a JSON array, for `json_each`
+    fn done_statuses(&self) -> String {
+        let done: Vec<&str> = self
+            .workflow
+            .statuses()
+            .iter()
+            .filter(|status| status.done)
+            .map(|status| status.name.as_str())
+            .collect();
+        serde_json::to_string(&done).unwrap_or_default()
+    }
+
+    /// Refuse a status change the workflow doesn't allow, like a constraint
+    fn check_transition(&self, from: &str, to: &str) -> Result<()> {
+        self.workflow.check(from, to).map_err(|message| {
+            Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_CONSTRAINT), Some(message))
+        })
+    }
+
+    /// The database file, None for an in-memory database
+    pub fn path(&self) -> Option<&Path> {
+        self.connection
+            .path()
+            .filter(|path| !path.is_empty())
+            .map(Path::new)
+    }
+
+    fn version(connection: &Connection) -> Result<usize> {
+        connection.query_row("PRAGMA user_version", [], |row| row.get(0))
+    }
+
+    /// Whether this is an existing database with an older schema. Databases
+    /// from before schema versions have a tasks table at version 0; only a
+    /// new, empty file needs no backup.
+    pub fn needs_migration(&self) -> Result<bool> {
+        let version = Self::version(&self.connection)?;
+        if version == 0 {
+            return self.connection.query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'tasks')",
+                [],
+                |row| row.get(0),
+            );
+        }
+        Ok(version < MIGRATIONS.len())
+    }
+
+    /// Whether every migration has run
+    pub fn is_current(&self) -> Result<bool> {
+        Ok(Self::version(&self.connection)? >= MIGRATIONS.len())
+    }
+
+    pub fn migrate(&self) -> Result<()> {
+        let version = Self::version(&self.connection)?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.connection.unchecked_transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Copy the whole database to a new file at `path`, encrypted with
+    /// `passphrase`, or as plain SQLite when it is empty
+    #[cfg(feature = "encryption")]
+    pub fn export(&self, path: &Path, passphrase: &str) -> Result<()> {
+        self.connection.execute(
+            "ATTACH DATABASE ?1 AS export KEY ?2",
+            params![path.to_string_lossy(), passphrase],
+        )?;
+        self.connection
+            .query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()))?;
+        // sqlcipher_export leaves out the schema version
+        let version: i64 = self
+            .connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        self.connection.pragma_update(
+            Some(rusqlite::DatabaseName::Attached("export")),
+            "user_version",
+            version,
+        )?;
+        self.connection.execute("DETACH DATABASE export", [])?;
+        Ok(())
+    }
+
+    /// Open another database file with this one's passphrase
+    fn open_copy(&self, path: &Path, flags: OpenFlags) -> Result<Connection> {
+        let connection = Connection::open_with_flags(path, flags)?;
+        if let Some(key) = &self.key {
+            connection.pragma_update(None, "key", key)?;
+        }
+        Ok(connection)
+    }
+
+    /// Copy the database to `path` with SQLite's online backup, which is
+    /// consistent even while the database is in use
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        let mut copy = self.open_copy(path, OpenFlags::default())?;
+        let backup = Backup::new(&self.connection, &mut copy)?;
+        backup.run_to_completion(256, Duration::ZERO, None)
+    }
+
+    /// Problems found in the backup at `path`, empty when it is intact and
+    /// a task database this version can open
+    pub fn check_backup(&self, path: &Path) -> Result<Vec<String>> {
+        let copy = self.open_copy(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = copy.prepare("PRAGMA integrity_check")?;
+        let mut problems: Vec<String> = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+        problems.retain(|problem| problem != "ok");
+        let version = Self::version(&copy)?;
+        if version == 0 {
+            problems.push("not a task database".to_string());
+        } else if version > MIGRATIONS.len() {
+            problems.push("made by a newer version of task_manager".to_string());
+        }
+        Ok(problems)
+    }
+
+    /// Replace the whole database with the backup at `path`, then bring its
+    /// schema up to date
+    pub fn restore_from(&mut self, path: &Path) -> Result<()> {
+        let copy = self.open_copy(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Backup::new(&copy, &mut self.connection)?.run_to_completion(256, Duration::ZERO, None)?;
+        self.migrate()
+    }
+
+    /// Change the passphrase of an encrypted database
+    #[cfg(feature = "encryption")]
+    pub fn rekey(&mut self, passphrase: &str) -> Result<()> {
+        self.connection.pragma_update(None, "rekey", passphrase)?;
+        self.key = Some(passphrase.to_string());
+        Ok(())
+    }
+
+    /// Run `f` in a transaction, committing only if it succeeds. Nested
+    /// calls join the outer transaction.
+    pub fn transaction<T, E: From<rusqlite::Error>>(
+        &self,
+        f: impl FnOnce(&Self) -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        if !self.connection.is_autocommit() {
+            return f(self);
+        }
+        let tx = self.connection.unchecked_transaction()?;
+        let value = f(self)?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    pub fn add_task(&self, task: &Task) -> Result<i32> {
+        let uuid = if task.uuid.is_empty() {
+            uuid::Uuid::new_v4().to_string()
+        } else {
+            task.uuid.clone()
+        };
+        let updated_at = now();
+        self.connection.execute(
+            "INSERT INTO tasks (title, description, category, parent_task_id, due_date, time, priority, status, uuid, updated_at, created_at, estimate) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10, ?11)",
             params![
                 task.title,
                 task.description,
@@ -50,10 +567,883 @@ impl Database {
                 task.time,
                 task.priority,
                 task.status, // Include status when adding a task
+                uuid,
+                updated_at,
+                task.estimate,
+            ],
+        )?;
+        let id = self.connection.last_insert_rowid() as i32;
+        self.set_tags(id, &task.tags)?;
+        self.touch_fields(id, &TRACKED_FIELDS, &updated_at)?;
+        Ok(id)
+    }
+
+    pub fn get_task(&self, id: i32) -> Result<Option<Task>> {
+        self.connection
+            .query_row(
+                &format!("SELECT {} FROM tasks WHERE id = ?1", TASK_COLUMNS),
+                params![id],
+                task_from_row,
+            )
+            .optional()
+    }
+
+    /// Every task that is neither archived nor trashed
+    pub fn get_all_tasks(&self) -> Result<Vec<Task>> {
+        self.get_tasks(Scope::Active)
+    }
+
+    pub fn get_tasks(&self, scope: Scope) -> Result<Vec<Task>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM tasks WHERE {} ORDER BY id",
+            TASK_COLUMNS,
+            scope.sql()
+        ))?;
+        let tasks = stmt
+            .query_map([], task_from_row)?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(tasks)
+    }
+
+    /// Ids of every task nested below `id`, at any depth
+    pub fn descendant_ids(&self, id: i32) -> Result<Vec<i32>> {
+        let mut stmt = self.connection.prepare(
+            "WITH RECURSIVE tree(id) AS (
+                SELECT id FROM tasks WHERE parent_task_id = ?1
+                UNION
+                SELECT tasks.id FROM tasks JOIN tree ON tasks.parent_task_id = tree.id
+            )
+            SELECT id FROM tree",
+        )?;
+        let ids = stmt
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ids)
+    }
+
+    pub fn update_task(&self, task: &Task) -> Result<()> {
+        let old = match self.get_task(task.id)? {
+            Some(old) => old,
+            None => return Ok(()),
+        };
+        // Archiving and trashing go through their own methods
+        let changed: Vec<&str> = TRACKED_FIELDS
+            .into_iter()
+            .filter(|field| !matches!(*field, "archived" | "trashed"))
+            .filter(|field| old.field(field) != task.field(field))
+            .collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        self.check_transition(&old.status, &task.status)?;
+        let updated_at = now();
+        self.connection.execute(
+            "UPDATE tasks SET title = ?1, description = ?2, category = ?3, parent_task_id = ?4, due_date = ?5, time = ?6, priority = ?7, status = ?8, updated_at = ?9, estimate = ?11 WHERE id = ?10",
+            params![
+                task.title,
+                task.description,
+                task.category,
+                task.parent_task_id,
+                task.due_date,
+                task.time,
+                task.priority,
+                task.status,
+                updated_at,
+                task.id,
+                task.estimate,
+            ],
+        )?;
+        self.set_tags(task.id, &task.tags)?;
+        self.touch_fields(task.id, &changed, &updated_at)?;
+        if self.workflow.is_done(&old.status) && !self.workflow.is_done(&task.status) {
+            self.reopen_parent(task.id)?;
+        }
+        Ok(())
+    }
+
+    /// A done parent is reopened with its sub-task, and so on up. This
+    /// doesn't wait for the workflow's transitions.
+    fn reopen_parent(&self, id: i32) -> Result<()> {
+        let Some(parent) = self.get_task(id)?.and_then(|task| task.parent_task_id) else {
+            return Ok(());
+        };
+        if self
+            .get_task(parent)?
+            .is_some_and(|parent| self.workflow.is_done(&parent.status))
+        {
+            self.write_status(parent, self.workflow.initial())?;
+        }
+        Ok(())
+    }
+
+    fn set_tags(&self, id: i32, tags: &[String]) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM task_tags WHERE task_id = ?1", params![id])?;
+        let mut stmt = self
+            .connection
+            .prepare("INSERT OR IGNORE INTO task_tags (task_id, tag) VALUES (?1, ?2)")?;
+        for tag in tags {
+            stmt.execute(params![id, tag])?;
+        }
+        Ok(())
+    }
+
+    /// Count the open, active tasks due before `today`, on it and from it
+    /// through `end_of_week`, using the due date index
+    pub fn due_counts(&self, today: NaiveDate, end_of_week: NaiveDate) -> Result<DueCounts> {
+        self.connection.query_row(
+            &format!(
+                "SELECT
+                    coalesce(sum(due_date < ?1), 0),
+                    coalesce(sum(due_date = ?1), 0),
+                    coalesce(sum(due_date >= ?1), 0)
+                FROM tasks
+                WHERE due_date <= ?2 AND due_date <> '' AND {}
+                    AND status NOT IN (SELECT value FROM json_each(?3))",
+                Scope::Active.sql()
+            ),
+            params![
+                today.format("%Y-%m-%d").to_string(),
+                end_of_week.format("%Y-%m-%d").to_string(),
+                self.done_statuses()
             ],
+            |row| {
+                Ok(DueCounts {
+                    overdue: row.get(0)?,
+                    today: row.get(1)?,
+                    week: row.get(2)?,
+                })
+            },
+        )
+    }
+
+    /// Ids of the tasks matching a `WHERE` clause built by `query::Query::to_sql`
+    pub fn find_task_ids(&self, where_sql: &str, params: &[Value]) -> Result<Vec<i32>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT id FROM tasks WHERE {} ORDER BY id",
+            where_sql
+        ))?;
+        let ids = stmt
+            .query_map(params_from_iter(params), |row| row.get(0))?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ids)
+    }
+
+    pub fn set_status(&self, id: i32, status: &str) -> Result<()> {
+        if let Some(task) = self.get_task(id)? {
+            self.check_transition(&task.status, status)?;
+        }
+        self.write_status(id, status)
+    }
+
+    fn write_status(&self, id: i32, status: &str) -> Result<()> {
+        let reopened = !self.workflow.is_done(status)
+            && self
+                .get_task(id)?
+                .is_some_and(|task| self.workflow.is_done(&task.status));
+        let updated_at = now();
+        let changed = self.connection.execute(
+            "UPDATE tasks SET status = ?1, updated_at = ?2 WHERE id = ?3 AND status IS NOT ?1",
+            params![status, updated_at, id],
+        )?;
+        if changed > 0 {
+            self.touch_fields(id, &["status"], &updated_at)?;
+            if reopened {
+                self.reopen_parent(id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set or clear `column` (archived_at or trashed_at) on a task and every
+    /// task below it
+    fn set_subtree(&self, id: i32, column: &str, field: &str, set: bool) -> Result<()> {
+        let updated_at = now();
+        let value = set.then_some(updated_at.as_str());
+        self.transaction(|db| {
+            let mut ids = db.descendant_ids(id)?;
+            ids.push(id);
+            for id in ids {
+                let changed = db.connection.execute(
+                    &format!(
+                        "UPDATE tasks SET {0} = ?1, updated_at = ?2 WHERE id = ?3 AND ({0} IS NULL) = ?4",
+                        column
+                    ),
+                    params![value, updated_at, id, set],
+                )?;
+                if changed > 0 {
+                    db.touch_fields(id, &[field], &updated_at)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Archive a task with its sub-tasks
+    pub fn archive_task(&self, id: i32) -> Result<()> {
+        self.set_subtree(id, "archived_at", "archived", true)
+    }
+
+    /// Move a task and its sub-tasks to the trash; `purge` deletes them for good
+    pub fn trash_task(&self, id: i32) -> Result<()> {
+        self.set_subtree(id, "trashed_at", "trashed", true)
+    }
+
+    /// Take a task and its sub-tasks out of the trash, or out of the archive
+    /// when it isn't trashed. A task whose parent stays hidden moves to the
+    /// top level.
+    pub fn restore_task(&self, id: i32) -> Result<()> {
+        let task = match self.get_task(id)? {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+        self.transaction(|db| {
+            if task.trashed_at.is_some() {
+                db.set_subtree(id, "trashed_at", "trashed", false)?;
+            } else if task.archived_at.is_some() {
+                db.set_subtree(id, "archived_at", "archived", false)?;
+            }
+            let parent = match task.parent_task_id {
+                Some(parent) => db.get_task(parent)?,
+                None => None,
+            };
+            if let (Some(parent), Some(restored)) = (parent, db.get_task(id)?) {
+                if parent.is_hidden() && !restored.is_hidden() {
+                    db.update_task(&Task {
+                        parent_task_id: None,
+                        ..restored
+                    })?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Archive done tasks whose status last changed more than `days` days
+    /// ago and that have no unfinished sub-tasks. Returns how many
+    /// tasks were archived, not counting sub-tasks.
+    pub fn auto_archive(&self, days: u32) -> Result<usize> {
+        let cutoff = (Utc::now() - chrono::Duration::days(days as i64))
+            .to_rfc3339_opts(SecondsFormat::Millis, true);
+        let mut stmt = self.connection.prepare(
+            "SELECT id FROM tasks
+            WHERE status IN (SELECT value FROM json_each(?2))
+                AND archived_at IS NULL AND trashed_at IS NULL
+                AND (SELECT updated_at FROM task_field_times
+                    WHERE task_id = tasks.id AND field = 'status') < ?1
+                AND NOT EXISTS (SELECT 1 FROM tasks AS sub
+                    WHERE sub.parent_task_id = tasks.id
+                        AND sub.status NOT IN (SELECT value FROM json_each(?2))
+                        AND sub.trashed_at IS NULL)
+            ORDER BY id",
+        )?;
+        let ids = stmt
+            .query_map(params![cutoff, self.done_statuses()], |row| row.get(0))?
+            .collect::<Result<Vec<i32>>>()?;
+        self.transaction(|db| {
+            let mut archived = 0;
+            for &id in &ids {
+                // Skip sub-tasks already archived with their parent
+                if db
+                    .get_task(id)?
+                    .is_some_and(|task| task.archived_at.is_none())
+                {
+                    db.archive_task(id)?;
+                    archived += 1;
+                }
+            }
+            Ok(archived)
+        })
+    }
+
+    /// Permanently delete the trashed tasks, and the archived ones too when
+    /// `archived` is set. Returns how many were deleted.
+    pub fn purge(&self, archived: bool) -> Result<usize> {
+        let scope = if archived {
+            "trashed_at IS NOT NULL OR archived_at IS NOT NULL"
+        } else {
+            "trashed_at IS NOT NULL"
+        };
+        let mut stmt = self
+            .connection
+            .prepare(&format!("SELECT id FROM tasks WHERE {}", scope))?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<i32>>>()?;
+        let deleted_at = now();
+        self.transaction(|db| {
+            for &id in &ids {
+                db.delete_tree(id, &deleted_at)?;
+            }
+            Ok(ids.len())
+        })
+    }
+
+    /// Delete a task together with all of its sub-tasks, leaving tombstones
+    /// so the deletion carries over on `merge`
+    pub fn delete_task(&self, id: i32) -> Result<()> {
+        self.delete_tree(id, &now())
+    }
+
+    fn delete_tree(&self, id: i32, deleted_at: &str) -> Result<()> {
+        const TREE: &str = "WITH RECURSIVE tree(id) AS (
+                SELECT ?1
+                UNION
+                SELECT tasks.id FROM tasks JOIN tree ON tasks.parent_task_id = tree.id
+            )";
+        self.transaction(|db| {
+            db.connection.execute(
+                &format!(
+                    "{} INSERT OR REPLACE INTO tombstones (uuid, deleted_at)
+                    SELECT uuid, ?2 FROM tasks WHERE id IN (SELECT id FROM tree)",
+                    TREE
+                ),
+                params![id, deleted_at],
+            )?;
+            for table in [
+                "task_field_times",
+                "task_tags",
+                "attachments",
+                "task_dependencies",
+                "commit_links",
+            ] {
+                db.connection.execute(
+                    &format!(
+                        "{} DELETE FROM {} WHERE task_id IN (SELECT id FROM tree)",
+                        TREE, table
+                    ),
+                    params![id],
+                )?;
+            }
+            db.connection.execute(
+                &format!(
+                    "{} DELETE FROM task_dependencies WHERE depends_on IN (SELECT id FROM tree)",
+                    TREE
+                ),
+                params![id],
+            )?;
+            db.connection.execute(
+                &format!(
+                    "{} DELETE FROM tasks WHERE id IN (SELECT id FROM tree)",
+                    TREE
+                ),
+                params![id],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn touch_fields(&self, id: i32, fields: &[&str], updated_at: &str) -> Result<()> {
+        let mut stmt = self.connection.prepare(
+            "INSERT OR REPLACE INTO task_field_times (task_id, field, updated_at) VALUES (?1, ?2, ?3)",
+        )?;
+        for field in fields {
+            stmt.execute(params![id, field, updated_at])?;
+        }
+        Ok(())
+    }
+
+    /// Ids of the tasks each task depends on, by task id
+    pub fn dependencies(&self) -> Result<HashMap<i32, Vec<i32>>> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT task_id, depends_on FROM task_dependencies ORDER BY depends_on")?;
+        let mut dependencies: HashMap<i32, Vec<i32>> = HashMap::new();
+        for row in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+            let (task_id, depends_on) = row?;
+            dependencies.entry(task_id).or_default().push(depends_on);
+        }
+        Ok(dependencies)
+    }
+
+    pub fn set_dependencies(&self, task_id: i32, depends_on: &[i32]) -> Result<()> {
+        self.transaction(|db| {
+            db.connection.execute(
+                "DELETE FROM task_dependencies WHERE task_id = ?1",
+                params![task_id],
+            )?;
+            for id in depends_on {
+                db.connection.execute(
+                    "INSERT OR IGNORE INTO task_dependencies (task_id, depends_on) VALUES (?1, ?2)",
+                    params![task_id, id],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Attachments of every task, by task id, oldest first
+    pub fn attachments(&self) -> Result<HashMap<i32, Vec<Attachment>>> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT id, task_id, target FROM attachments ORDER BY id")?;
+        let mut attachments: HashMap<i32, Vec<Attachment>> = HashMap::new();
+        for attachment in stmt.query_map([], |row| {
+            Ok(Attachment {
+                id: row.get(0)?,
+                task_id: row.get(1)?,
+                target: row.get(2)?,
+            })
+        })? {
+            let attachment = attachment?;
+            attachments
+                .entry(attachment.task_id)
+                .or_default()
+                .push(attachment);
+        }
+        Ok(attachments)
+    }
+
+    /// Attach a URL or path; attaching the same target twice does nothing
+    pub fn add_attachment(&self, task_id: i32, target: &str) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR IGNORE INTO attachments (task_id, target, created_at) VALUES (?1, ?2, ?3)",
+            params![task_id, target, now()],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_attachment(&self, id: i32) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM attachments WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Commits linked to every task, by task id, newest first
+    pub fn commit_links(&self) -> Result<HashMap<i32, Vec<CommitLink>>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT task_id, hash, branch, summary, author, committed_at, closes FROM commit_links
+            ORDER BY committed_at DESC",
+        )?;
+        let mut links: HashMap<i32, Vec<CommitLink>> = HashMap::new();
+        for link in stmt.query_map([], |row| {
+            Ok(CommitLink {
+                task_id: row.get(0)?,
+                hash: row.get(1)?,
+                branch: row.get(2)?,
+                summary: row.get(3)?,
+                author: row.get(4)?,
+                committed_at: row.get(5)?,
+                closes: row.get(6)?,
+            })
+        })? {
+            let link = link?;
+            links.entry(link.task_id).or_default().push(link);
+        }
+        Ok(links)
+    }
+
+    /// Record a commit link. Returns false when it was already recorded.
+    pub fn add_commit_link(&self, link: &CommitLink) -> Result<bool> {
+        let added = self.connection.execute(
+            "INSERT OR IGNORE INTO commit_links (task_id, hash, branch, summary, author, committed_at, closes)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                link.task_id,
+                link.hash,
+                link.branch,
+                link.summary,
+                link.author,
+                link.committed_at,
+                link.closes
+            ],
+        )?;
+        Ok(added > 0)
+    }
+
+    /// Every task with its parent uuid and per-field change times
+    pub fn sync_records(&self) -> Result<Vec<SyncRecord>> {
+        let tasks = self.get_tasks(Scope::All)?;
+        let uuids: HashMap<i32, String> = tasks.iter().map(|t| (t.id, t.uuid.clone())).collect();
+        let mut stmt = self
+            .connection
+            .prepare("SELECT task_id, field, updated_at FROM task_field_times")?;
+        let mut times: HashMap<i32, HashMap<String, String>> = HashMap::new();
+        for row in stmt.query_map([], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get(1)?, row.get(2)?))
+        })? {
+            let (id, field, updated_at) = row?;
+            times.entry(id).or_default().insert(field, updated_at);
+        }
+        Ok(tasks
+            .into_iter()
+            .map(|task| SyncRecord {
+                parent_uuid: task.parent_task_id.and_then(|id| uuids.get(&id).cloned()),
+                field_times: times.remove(&task.id).unwrap_or_default(),
+                task,
+            })
+            .collect())
+    }
+
+    /// Insert or update a task by uuid, keeping the record's change times.
+    /// The parent is linked separately with `set_parent_uuid` once every
+    /// record exists.
+    pub fn upsert_sync_record(&self, record: &SyncRecord) -> Result<()> {
+        let task = &record.task;
+        let existing: Option<i32> = self
+            .connection
+            .query_row(
+                "SELECT id FROM tasks WHERE uuid = ?1",
+                params![task.uuid],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let id = match existing {
+            Some(id) => {
+                self.connection.execute(
+                    "UPDATE tasks SET title = ?1, description = ?2, category = ?3, due_date = ?4, time = ?5, priority = ?6, status = ?7, updated_at = ?8, archived_at = ?9, trashed_at = ?10, estimate = ?12 WHERE id = ?11",
+                    params![
+                        task.title,
+                        task.description,
+                        task.category,
+                        task.due_date,
+                        task.time,
+                        task.priority,
+                        task.status,
+                        task.updated_at,
+                        task.archived_at,
+                        task.trashed_at,
+                        id,
+                        task.estimate,
+                    ],
+                )?;
+                id
+            }
+            None => {
+                self.connection.execute(
+                    "INSERT INTO tasks (title, description, category, due_date, time, priority, status, uuid, updated_at, created_at, archived_at, trashed_at, estimate) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, coalesce(nullif(?10, ''), ?9), ?11, ?12, ?13)",
+                    params![
+                        task.title,
+                        task.description,
+                        task.category,
+                        task.due_date,
+                        task.time,
+                        task.priority,
+                        task.status,
+                        task.uuid,
+                        task.updated_at,
+                        task.created_at,
+                        task.archived_at,
+                        task.trashed_at,
+                        task.estimate,
+                    ],
+                )?;
+                self.connection.last_insert_rowid() as i32
+            }
+        };
+        self.connection
+            .execute("DELETE FROM tombstones WHERE uuid = ?1", params![task.uuid])?;
+        self.set_tags(id, &task.tags)?;
+        for (field, updated_at) in &record.field_times {
+            self.touch_fields(id, &[field], updated_at)?;
+        }
+        Ok(())
+    }
+
+    /// Put tasks back the way `records` describe them, re-creating deleted
+    /// ones. Every field is stamped as changed now, so an undo wins the next
+    /// merge.
+    pub fn restore(&self, records: &[SyncRecord]) -> Result<()> {
+        let restored_at = now();
+        self.transaction(|db| {
+            for record in records {
+                let mut record = record.clone();
+                record.task.updated_at = restored_at.clone();
+                record.field_times = TRACKED_FIELDS
+                    .iter()
+                    .map(|field| (field.to_string(), restored_at.clone()))
+                    .collect();
+                db.upsert_sync_record(&record)?;
+            }
+            for record in records {
+                db.set_parent_uuid(&record.task.uuid, record.parent_uuid.as_deref())?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn set_parent_uuid(&self, uuid: &str, parent_uuid: Option<&str>) -> Result<()> {
+        self.connection.execute(
+            "UPDATE tasks SET parent_task_id = (SELECT id FROM tasks WHERE uuid = ?2) WHERE uuid = ?1",
+            params![uuid, parent_uuid],
+        )?;
+        Ok(())
+    }
+
+    /// uuid -> deletion time of every deleted task
+    pub fn tombstones(&self) -> Result<HashMap<String, String>> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT uuid, deleted_at FROM tombstones")?;
+        let tombstones = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(tombstones)
+    }
+
+    /// Apply a deletion made elsewhere; records the tombstone even when the
+    /// task never existed here so it keeps propagating
+    pub fn delete_by_uuid(&self, uuid: &str, deleted_at: &str) -> Result<()> {
+        let id: Option<i32> = self
+            .connection
+            .query_row(
+                "SELECT id FROM tasks WHERE uuid = ?1",
+                params![uuid],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(id) = id {
+            self.delete_tree(id, deleted_at)?;
+        }
+        self.connection.execute(
+            "INSERT OR REPLACE INTO tombstones (uuid, deleted_at) VALUES (?1, ?2)",
+            params![uuid, deleted_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn meta(&self, key: &str) -> Result<Option<String>> {
+        self.connection
+            .query_row(
+                "SELECT value FROM sync_meta WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    pub fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO sync_meta (key, value) VALUES (?1, ?2)",
+            params![key, value],
         )?;
         Ok(())
     }
 
-    // Additional methods for fetching tasks, etc., can be added here
+    pub fn saved_views(&self) -> Result<Vec<SavedView>> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT id, name, query, sort, group_by FROM saved_views ORDER BY id")?;
+        let views = stmt
+            .query_map([], |row| {
+                Ok(SavedView {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    query: row.get(2)?,
+                    sort: row.get(3)?,
+                    group_by: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(views)
+    }
+
+    /// Create a view, or replace the one with the same name
+    pub fn save_view(&self, name: &str, query: &str, sort: &str, group_by: &str) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO saved_views (name, query, sort, group_by) VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (name) DO UPDATE SET query = ?2, sort = ?3, group_by = ?4",
+            params![name, query, sort, group_by],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_view(&self, id: i32) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM saved_views WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> Database {
+        let db = Database::new(":memory:").unwrap();
+        db.migrate().unwrap();
+        db
+    }
+
+    fn add(db: &Database, title: &str, parent: Option<i32>, status: &str) -> i32 {
+        db.add_task(&Task {
+            title: title.to_string(),
+            parent_task_id: parent,
+            status: status.to_string(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn get(db: &Database, id: i32) -> Option<Task> {
+        db.get_task(id).unwrap()
+    }
+
+    /// Pretend the status of `id` last changed `days` days ago
+    fn status_changed(db: &Database, id: i32, days: i64) {
+        let at = (Utc::now() - chrono::Duration::days(days))
+            .to_rfc3339_opts(SecondsFormat::Millis, true);
+        db.touch_fields(id, &["status"], &at).unwrap();
+    }
+
+    #[test]
+    fn archives_tasks_completed_before_the_cutoff() {
+        let db = database();
+        let old = add(&db, "Old", None, "Completed");
+        let recent = add(&db, "Recent", None, "Completed");
+        let open = add(&db, "Open", None, "To Do");
+        let parent = add(&db, "Parent", None, "Completed");
+        let child = add(&db, "Child", Some(parent), "To Do");
+        for id in [old, open, parent, child] {
+            status_changed(&db, id, 10);
+        }
+        status_changed(&db, recent, 2);
+
+        assert_eq!(db.auto_archive(7).unwrap(), 1);
+        assert!(get(&db, old).unwrap().archived_at.is_some());
+        for id in [recent, open, parent, child] {
+            assert!(get(&db, id).unwrap().archived_at.is_none(), "task {}", id);
+        }
+
+        // Once the sub-task is done too, the parent goes with it
+        db.set_status(child, "Completed").unwrap();
+        status_changed(&db, child, 10);
+        assert_eq!(db.auto_archive(7).unwrap(), 1);
+        assert!(get(&db, parent).unwrap().archived_at.is_some());
+        assert!(get(&db, child).unwrap().archived_at.is_some());
+    }
+
+    #[test]
+    fn purge_deletes_only_trashed_subtrees() {
+        let db = database();
+        let trashed = add(&db, "Trashed", None, "To Do");
+        let trashed_child = add(&db, "Trashed child", Some(trashed), "To Do");
+        let kept = add(&db, "Kept", None, "To Do");
+        let kept_child = add(&db, "Kept child", Some(kept), "To Do");
+        let archived = add(&db, "Archived", None, "Completed");
+        db.trash_task(trashed).unwrap();
+        db.trash_task(kept_child).unwrap();
+        db.archive_task(archived).unwrap();
+
+        db.purge(false).unwrap();
+        assert!(get(&db, trashed).is_none());
+        assert!(get(&db, trashed_child).is_none());
+        assert!(get(&db, kept_child).is_none());
+        assert!(get(&db, kept).is_some());
+        assert!(get(&db, archived).is_some());
+        assert_eq!(db.tombstones().unwrap().len(), 3);
+
+        db.purge(true).unwrap();
+        assert!(get(&db, archived).is_none());
+        assert!(get(&db, kept).is_some());
+    }
+
+    #[test]
+    fn reopening_a_sub_task_reopens_done_parents() {
+        let db = database();
+        let top = add(&db, "Top", None, "Completed");
+        let parent = add(&db, "Parent", Some(top), "Completed");
+        let child = add(&db, "Child", Some(parent), "Completed");
+        let sibling = add(&db, "Sibling", None, "Completed");
+        let sibling_child = add(&db, "Sibling child", Some(sibling), "Completed");
+
+        db.set_status(child, "In Progress").unwrap();
+        assert_eq!(get(&db, parent).unwrap().status, "To Do");
+        assert_eq!(get(&db, top).unwrap().status, "To Do");
+
+        // Through the task dialog too
+        db.update_task(&Task {
+            status: "To Do".to_string(),
+            ..get(&db, sibling_child).unwrap()
+        })
+        .unwrap();
+        assert_eq!(get(&db, sibling).unwrap().status, "To Do");
+
+        // Moving between open statuses leaves the parent alone
+        db.set_status(parent, "Completed").unwrap();
+        db.set_status(child, "To Do").unwrap();
+        assert_eq!(get(&db, parent).unwrap().status, "Completed");
+    }
+
+    #[test]
+    fn restores_a_backup() {
+        let mut db = database();
+        let parent = add(&db, "Plan trip", None, "To Do");
+        let child = add(&db, "Book flights", Some(parent), "Completed");
+        let path =
+            std::env::temp_dir().join(format!("task_manager_backup_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        db.backup_to(&path).unwrap();
+        let problems = db.check_backup(&path).unwrap();
+
+        db.delete_task(parent).unwrap();
+        add(&db, "After the backup", None, "To Do");
+        let restored = db.restore_from(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(problems.is_empty(), "{:?}", problems);
+        restored.unwrap();
+
+        let tasks: Vec<(i32, String, Option<i32>)> = db
+            .get_tasks(Scope::All)
+            .unwrap()
+            .into_iter()
+            .map(|task| (task.id, task.title, task.parent_task_id))
+            .collect();
+        assert_eq!(
+            tasks,
+            [
+                (parent, "Plan trip".to_string(), None),
+                (child, "Book flights".to_string(), Some(parent)),
+            ]
+        );
+        assert!(db.tombstones().unwrap().is_empty());
+    }
+
+    /// Queries of the saved views that filter on status
+    fn status_views(db: &Database) -> Vec<(String, String)> {
+        db.saved_views()
+            .unwrap()
+            .into_iter()
+            .filter(|view| view.query.contains("status:"))
+            .map(|view| (view.name, view.query))
+            .collect()
+    }
+
+    #[test]
+    fn default_views_hide_every_done_status() {
+        let db = database();
+        let views = status_views(&db);
+        assert_eq!(views.len(), 3);
+        for (name, query) in views {
+            assert!(query.ends_with("-status:done"), "{}: {}", name, query);
+        }
+
+        // A database from before migration 12 with one view edited
+        db.connection
+            .execute_batch(
+                "UPDATE saved_views SET query = replace(query, '-status:done', '-status:completed');
+                UPDATE saved_views SET query = 'due<today -status:completed pri:high'
+                    WHERE name = 'Overdue';
+                PRAGMA user_version = 11;",
+            )
+            .unwrap();
+        db.migrate().unwrap();
+        assert_eq!(
+            status_views(&db),
+            [
+                (
+                    "Due this week".to_string(),
+                    "due<=eow -status:done".to_string()
+                ),
+                (
+                    "High priority work".to_string(),
+                    "pri:high cat:work -status:done".to_string()
+                ),
+                (
+                    "Overdue".to_string(),
+                    "due<today -status:completed pri:high".to_string()
+                ),
+            ]
+        );
+    }
 }
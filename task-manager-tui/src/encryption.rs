@@ -0,0 +1,273 @@
+//! Opt-in SQLCipher encryption of task databases, available when built with
+//! `--features encryption`: opening with a passphrase, and `encrypt`,
+//! `decrypt` and `rekey`, which convert a database in place.
+
+use crate::db::Database;
+#[cfg(feature = "encryption")]
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute, terminal,
+};
+use std::fs::File;
+#[cfg(feature = "encryption")]
+use std::io::Write;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[cfg(not(feature = "encryption"))]
+const UNSUPPORTED: &str = "encryption needs a build with `--features encryption`";
+
+/// Passphrase used instead of prompting, e.g. for `serve`
+#[cfg(feature = "encryption")]
+const PASSPHRASE_VAR: &str = "TASK_MANAGER_PASSPHRASE";
+/// New passphrase for `encrypt` and `rekey` without prompting
+#[cfg(feature = "encryption")]
+const NEW_PASSPHRASE_VAR: &str = "TASK_MANAGER_NEW_PASSPHRASE";
+
+/// Whether `path` exists and is not a plain SQLite file
+pub fn is_encrypted(path: &Path) -> io::Result<bool> {
+    const HEADER: &[u8] = b"SQLite format 3\0";
+    let mut header = Vec::new();
+    match File::open(path) {
+        Ok(file) => file.take(HEADER.len() as u64).read_to_end(&mut header)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    Ok(!header.is_empty() && !HEADER.starts_with(&header))
+}
+
+/// Read a passphrase from the terminal without echoing it. None when the
+/// user cancels with Esc or Ctrl+C.
+#[cfg(feature = "encryption")]
+fn prompt_passphrase(label: &str) -> io::Result<Option<String>> {
+    let in_tui = terminal::is_raw_mode_enabled()?;
+    let mut stdout = io::stdout();
+    if in_tui {
+        execute!(
+            stdout,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+    } else {
+        terminal::enable_raw_mode()?;
+    }
+    write!(stdout, "{}", label)?;
+    stdout.flush()?;
+
+    let mut passphrase = String::new();
+    let result = loop {
+        match event::read()? {
+            Event::Key(key) => match key.code {
+                KeyCode::Enter => break Some(passphrase),
+                KeyCode::Esc => break None,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break None,
+                KeyCode::Backspace => {
+                    passphrase.pop();
+                }
+                KeyCode::Char(c) => passphrase.push(c),
+                _ => {}
+            },
+            Event::Paste(text) => passphrase.push_str(&text),
+            _ => {}
+        }
+    };
+    write!(stdout, "\r\n")?;
+    if !in_tui {
+        terminal::disable_raw_mode()?;
+    }
+    Ok(result)
+}
+
+/// Open a task database, asking for its passphrase if it is encrypted. Call
+/// `migrate` before using it.
+pub fn open(path: &Path) -> Result<Database, Box<dyn std::error::Error>> {
+    if is_encrypted(path)? {
+        open_encrypted(path)
+    } else {
+        Ok(Database::new(path)?)
+    }
+}
+
+/// Open a task database read-only, without migrating it. An encrypted one
+/// takes its passphrase from the environment, or from a prompt when `ask`
+/// is set.
+pub fn open_read_only(path: &Path, ask: bool) -> Result<Database, Box<dyn std::error::Error>> {
+    if is_encrypted(path)? {
+        open_encrypted_read_only(path, ask)
+    } else {
+        Ok(Database::open_read_only(path, None)?)
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open_encrypted_read_only(
+    path: &Path,
+    _ask: bool,
+) -> Result<Database, Box<dyn std::error::Error>> {
+    open_encrypted(path)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open_encrypted(path: &Path) -> Result<Database, Box<dyn std::error::Error>> {
+    Err(format!(
+        "{} is encrypted or not a task database; {}",
+        path.display(),
+        UNSUPPORTED
+    )
+    .into())
+}
+
+#[cfg(feature = "encryption")]
+fn is_wrong_passphrase(e: &rusqlite::Error) -> bool {
+    matches!(e, rusqlite::Error::SqliteFailure(failure, _)
+        if failure.code == rusqlite::ErrorCode::NotADatabase)
+}
+
+#[cfg(feature = "encryption")]
+fn open_encrypted(path: &Path) -> Result<Database, Box<dyn std::error::Error>> {
+    unlock(path, true, |passphrase| {
+        Database::open_encrypted(path, passphrase)
+    })
+}
+
+#[cfg(feature = "encryption")]
+fn open_encrypted_read_only(
+    path: &Path,
+    ask: bool,
+) -> Result<Database, Box<dyn std::error::Error>> {
+    unlock(path, ask, |passphrase| {
+        Database::open_read_only(path, Some(passphrase))
+    })
+}
+
+/// Open with the passphrase from the environment, or else from up to three
+/// prompts when `ask` is set
+#[cfg(feature = "encryption")]
+fn unlock(
+    path: &Path,
+    ask: bool,
+    open: impl Fn(&str) -> rusqlite::Result<Database>,
+) -> Result<Database, Box<dyn std::error::Error>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) {
+        return open(&passphrase).map_err(|e| {
+            if is_wrong_passphrase(&e) {
+                format!(
+                    "wrong passphrase for {} in ${}",
+                    path.display(),
+                    PASSPHRASE_VAR
+                )
+                .into()
+            } else {
+                e.into()
+            }
+        });
+    }
+    if !ask {
+        return Err(format!("{} is encrypted; set ${}", path.display(), PASSPHRASE_VAR).into());
+    }
+    let mut label = format!("Passphrase for {}: ", path.display());
+    for _ in 0..3 {
+        let passphrase = prompt_passphrase(&label)?.ok_or("cancelled")?;
+        match open(&passphrase) {
+            Err(e) if is_wrong_passphrase(&e) => {
+                label = format!("Wrong passphrase. Passphrase for {}: ", path.display())
+            }
+            result => return Ok(result?),
+        }
+    }
+    Err(format!("wrong passphrase for {}", path.display()).into())
+}
+
+/// Ask for a new passphrase twice, unless it is given in the environment
+#[cfg(feature = "encryption")]
+fn new_passphrase() -> Result<String, Box<dyn std::error::Error>> {
+    if let Ok(passphrase) = std::env::var(NEW_PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+    let passphrase = prompt_passphrase("New passphrase: ")?.ok_or("cancelled")?;
+    if passphrase.is_empty() {
+        return Err("the passphrase must not be empty".into());
+    }
+    if prompt_passphrase("Repeat the new passphrase: ")?.as_ref() != Some(&passphrase) {
+        return Err("the passphrases don't match".into());
+    }
+    Ok(passphrase)
+}
+
+/// Replace the database at `path` with an exported copy, written next to it
+/// first so a failure leaves the original untouched
+#[cfg(feature = "encryption")]
+fn convert(path: &Path, db: Database, passphrase: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut converted = path.as_os_str().to_owned();
+    converted.push(".converting");
+    let converted = std::path::PathBuf::from(converted);
+    if converted.exists() {
+        std::fs::remove_file(&converted)?;
+    }
+    db.export(&converted, passphrase)?;
+    // Closing checkpoints the write-ahead log, which must not outlive the
+    // file it belongs to
+    drop(db);
+    std::fs::rename(&converted, path)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        match std::fs::remove_file(sidecar) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Encrypt a plain database in place
+#[cfg(feature = "encryption")]
+pub fn encrypt(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if is_encrypted(path)? {
+        return Err(format!("{} is already encrypted", path.display()).into());
+    }
+    let db = Database::new(path)?;
+    let passphrase = new_passphrase()?;
+    convert(path, db, &passphrase)?;
+    println!("Encrypted {}", path.display());
+    Ok(())
+}
+
+/// Turn an encrypted database back into plain SQLite
+#[cfg(feature = "encryption")]
+pub fn decrypt(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if !is_encrypted(path)? {
+        return Err(format!("{} is not encrypted", path.display()).into());
+    }
+    convert(path, open_encrypted(path)?, "")?;
+    println!("Decrypted {}", path.display());
+    Ok(())
+}
+
+/// Change the passphrase of an encrypted database
+#[cfg(feature = "encryption")]
+pub fn rekey(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if !is_encrypted(path)? {
+        return Err(format!("{} is not encrypted; use `encrypt`", path.display()).into());
+    }
+    let mut db = open_encrypted(path)?;
+    db.rekey(&new_passphrase()?)?;
+    println!("Changed the passphrase of {}", path.display());
+    Ok(())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn encrypt(_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Err(UNSUPPORTED.into())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn decrypt(_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Err(UNSUPPORTED.into())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn rekey(_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Err(UNSUPPORTED.into())
+}
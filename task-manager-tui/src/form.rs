@@ -0,0 +1,943 @@
+//! The add/edit task form: field focus, choice and date pickers, and
+//! validation before saving.
+
+use crate::db::{self, Task};
+use crate::query::{self, DateValue};
+use crate::textarea::TextArea;
+use crate::theme::Theme;
+use crate::workflow::Workflow;
+use chrono::{Datelike, Months, NaiveDate, NaiveTime};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs},
+    Frame,
+};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    Title,
+    Description,
+    Category,
+    ParentTask,
+    DueDate,
+    Time,
+    Estimate,
+    Priority,
+    Status,
+    Tags,
+    Save,
+    Cancel,
+}
+
+/// Tab order
+const FIELDS: [Field; 12] = [
+    Field::Title,
+    Field::Description,
+    Field::Category,
+    Field::ParentTask,
+    Field::DueDate,
+    Field::Time,
+    Field::Estimate,
+    Field::Priority,
+    Field::Status,
+    Field::Tags,
+    Field::Save,
+    Field::Cancel,
+];
+
+/// What the dialog should do after a key
+pub enum Outcome {
+    Continue,
+    Save(Box<Task>),
+    Cancel,
+    /// Hand the description to `$EDITOR`
+    OpenEditor,
+}
+
+/// One of a fixed set of values, picked with the arrow keys
+struct Choice {
+    options: Vec<String>,
+    selected: usize,
+}
+
+impl Choice {
+    /// A current value outside `options` (e.g. a custom category set
+    /// through a bulk edit) is kept as an extra option
+    fn new(options: &[&str], current: Option<&str>) -> Self {
+        let mut options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+        let selected = match current {
+            Some(current) => options
+                .iter()
+                .position(|o| o == current)
+                .unwrap_or_else(|| {
+                    options.push(current.to_string());
+                    options.len() - 1
+                }),
+            None => 0,
+        };
+        Choice { options, selected }
+    }
+
+    fn value(&self) -> &str {
+        &self.options[self.selected]
+    }
+
+    fn step(&mut self, delta: isize) {
+        let len = self.options.len() as isize;
+        self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+    }
+
+    /// The option drawn `x` columns into the field, where `Tabs` pads each
+    /// with a space on both sides and puts a divider between them
+    fn option_at(&self, x: u16) -> Option<usize> {
+        let mut start = 0;
+        for (i, option) in self.options.iter().enumerate() {
+            let end = start + option.chars().count() as u16 + 2;
+            if (start..end).contains(&x) {
+                return Some(i);
+            }
+            start = end + 1;
+        }
+        None
+    }
+}
+
+/// Type-ahead search over the tasks the edited task can be nested under
+struct ParentPicker {
+    /// Id and hierarchy path ("Move house / Pack / Books") of each candidate
+    candidates: Vec<(i32, String)>,
+    query: String,
+    /// Index into `matches()`, where 0 is the top level
+    selected: usize,
+}
+
+impl ParentPicker {
+    /// Every visible task except `task_id` itself and its descendants
+    fn new(tasks: &[Task], task_id: Option<i32>) -> Self {
+        let by_id: HashMap<i32, &Task> = tasks.iter().map(|task| (task.id, task)).collect();
+        let path = |task: &Task| {
+            let mut titles = vec![task.title.as_str()];
+            let mut seen = HashSet::from([task.id]);
+            let mut parent = task.parent_task_id;
+            while let Some(next) = parent.and_then(|id| by_id.get(&id)) {
+                if !seen.insert(next.id) {
+                    break;
+                }
+                titles.push(next.title.as_str());
+                parent = next.parent_task_id;
+            }
+            titles.reverse();
+            titles.join(" / ")
+        };
+        let mut excluded: HashSet<i32> = task_id.into_iter().collect();
+        // Repeat until no child of an excluded task is left
+        loop {
+            let before = excluded.len();
+            for task in tasks {
+                if task.parent_task_id.is_some_and(|p| excluded.contains(&p)) {
+                    excluded.insert(task.id);
+                }
+            }
+            if excluded.len() == before {
+                break;
+            }
+        }
+        let mut candidates: Vec<(i32, String)> = tasks
+            .iter()
+            .filter(|task| !task.is_hidden() && !excluded.contains(&task.id))
+            .map(|task| (task.id, path(task)))
+            .collect();
+        candidates.sort_by_key(|(_, path)| path.to_lowercase());
+        ParentPicker {
+            candidates,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// The top level, then the candidates matching the query, best first
+    fn matches(&self) -> Vec<(Option<i32>, &str)> {
+        let mut matches = vec![(None, "None (top level)")];
+        if self.query.is_empty() {
+            matches.extend(
+                self.candidates
+                    .iter()
+                    .map(|(id, path)| (Some(*id), path.as_str())),
+            );
+        } else {
+            let matcher = SkimMatcherV2::default();
+            let mut scored: Vec<(i64, i32, &str)> = self
+                .candidates
+                .iter()
+                .filter_map(|(id, path)| {
+                    matcher
+                        .fuzzy_match(path, &self.query)
+                        .map(|score| (score, *id, path.as_str()))
+                })
+                .collect();
+            scored.sort_by_key(|(score, _, _)| std::cmp::Reverse(*score));
+            matches.extend(scored.into_iter().map(|(_, id, path)| (Some(id), path)));
+        }
+        matches
+    }
+
+    fn path(&self, id: i32) -> Option<&str> {
+        self.candidates
+            .iter()
+            .find(|(candidate, _)| *candidate == id)
+            .map(|(_, path)| path.as_str())
+    }
+
+    fn set_query(&mut self, query: String) {
+        self.query = query;
+        // Jump to the best match, keeping the top level reachable above it
+        self.selected = if self.query.is_empty() || self.matches().len() == 1 {
+            0
+        } else {
+            1
+        };
+    }
+
+    fn step(&mut self, delta: isize) {
+        let len = self.matches().len() as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, len - 1) as usize;
+    }
+}
+
+pub struct TaskForm {
+    /// The task being edited, or a blank one
+    task: Task,
+    /// Decides which statuses the task can move to
+    workflow: Workflow,
+    title: String,
+    description: TextArea,
+    category: Choice,
+    priority: Choice,
+    status: Choice,
+    parent: Option<i32>,
+    parent_picker: ParentPicker,
+    due_date: String,
+    time: String,
+    estimate: String,
+    tags: String,
+    focus: Field,
+    /// Set by the first save attempt; problems are then kept up to date
+    validating: bool,
+    problems: Vec<(Field, String)>,
+    editor_error: Option<String>,
+    /// Where each field was last drawn, for mouse clicks
+    areas: Vec<(Field, Rect)>,
+    /// Inside of the parent matches list and its scroll offset, while shown
+    parent_list: Option<(Rect, usize)>,
+    /// Inside of the calendar and the first of the month it shows, while
+    /// shown
+    calendar: Option<(Rect, NaiveDate)>,
+}
+
+impl TaskForm {
+    /// `tasks` are the candidates for the parent task
+    pub fn new(existing: Option<&Task>, tasks: &[Task], workflow: &Workflow) -> Self {
+        let task = existing.cloned().unwrap_or_default();
+        TaskForm {
+            title: task.title.clone(),
+            description: TextArea::new(&task.description),
+            category: Choice::new(&db::CATEGORIES, existing.map(|t| t.category.as_str())),
+            priority: Choice::new(&db::PRIORITIES, existing.map(|t| t.priority.as_str())),
+            status: Choice::new(&workflow.names(), existing.map(|t| t.status.as_str())),
+            workflow: workflow.clone(),
+            parent: task.parent_task_id,
+            parent_picker: ParentPicker::new(tasks, existing.map(|t| t.id)),
+            due_date: task.due_date.clone().unwrap_or_default(),
+            time: task.time.clone().unwrap_or_default(),
+            estimate: task.estimate.map(|e| e.to_string()).unwrap_or_default(),
+            tags: task.tags.join(" "),
+            focus: Field::Title,
+            validating: false,
+            problems: Vec::new(),
+            editor_error: None,
+            areas: Vec::new(),
+            parent_list: None,
+            calendar: None,
+            task,
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        self.description.text()
+    }
+
+    /// Take the result of editing the description in `$EDITOR`
+    pub fn finish_editing(&mut self, edited: Result<String, String>) {
+        match edited {
+            Ok(text) => {
+                self.description.set_text(&text);
+                self.editor_error = None;
+            }
+            Err(e) => self.editor_error = Some(e),
+        }
+    }
+
+    /// Problems that keep the form from being saved, by field
+    fn check(&self, today: NaiveDate) -> Vec<(Field, String)> {
+        let mut problems = Vec::new();
+        if self.title.trim().is_empty() {
+            problems.push((Field::Title, "a title is required".to_string()));
+        }
+        if self.due_date(today).is_err() {
+            problems.push((
+                Field::DueDate,
+                "use YYYY-MM-DD, today, tomorrow, eow, +Nd or +Nw".to_string(),
+            ));
+        }
+        let time = self.time.trim();
+        if !time.is_empty() && NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+            problems.push((Field::Time, "use HH:MM (24-hour)".to_string()));
+        }
+        if self.estimate().is_err() {
+            problems.push((Field::Estimate, "use a number like 1.5".to_string()));
+        }
+        // New tasks may start in any status
+        if self.task.id != 0 {
+            if let Err(e) = self.workflow.check(&self.task.status, self.status.value()) {
+                problems.push((Field::Status, e));
+            }
+        }
+        problems
+    }
+
+    fn problem(&self, field: Field) -> Option<&str> {
+        self.problems
+            .iter()
+            .find(|(f, _)| *f == field)
+            .map(|(_, problem)| problem.as_str())
+    }
+
+    /// The typed due date, resolved against today. Empty or "none" is no date.
+    fn due_date(&self, today: NaiveDate) -> Result<Option<NaiveDate>, ()> {
+        let due = self.due_date.trim();
+        if due.is_empty() {
+            return Ok(None);
+        }
+        match query::parse_date(due) {
+            Some(DateValue::None) => Ok(None),
+            Some(date) => Ok(date.resolve(today)),
+            None => Err(()),
+        }
+    }
+
+    /// The typed estimate; empty is none
+    fn estimate(&self) -> Result<Option<f64>, ()> {
+        let estimate = self.estimate.trim();
+        if estimate.is_empty() {
+            return Ok(None);
+        }
+        match estimate.parse::<f64>() {
+            Ok(estimate) if estimate.is_finite() && estimate >= 0.0 => Ok(Some(estimate)),
+            _ => Err(()),
+        }
+    }
+
+    /// Move the due date with the date picker, starting from today when
+    /// none is set yet
+    fn shift_due_date(&mut self, today: NaiveDate, shift: impl Fn(NaiveDate) -> Option<NaiveDate>) {
+        let date = self.due_date(today).ok().flatten().unwrap_or(today);
+        if let Some(date) = shift(date) {
+            self.due_date = date.format("%Y-%m-%d").to_string();
+        }
+    }
+
+    fn build(&self, today: NaiveDate) -> Task {
+        let optional = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+        Task {
+            title: self.title.trim().to_string(),
+            description: self.description.text().to_string(),
+            category: self.category.value().to_string(),
+            parent_task_id: self.parent,
+            due_date: self
+                .due_date(today)
+                .ok()
+                .flatten()
+                .map(|date| date.format("%Y-%m-%d").to_string()),
+            time: optional(&self.time),
+            estimate: self.estimate().ok().flatten(),
+            priority: self.priority.value().to_string(),
+            status: self.status.value().to_string(),
+            tags: db::parse_tags(&self.tags),
+            ..self.task.clone()
+        }
+    }
+
+    fn save(&mut self, today: NaiveDate) -> Outcome {
+        self.validating = true;
+        self.problems = self.check(today);
+        match self.problems.first() {
+            None => Outcome::Save(Box::new(self.build(today))),
+            Some((field, _)) => {
+                self.focus = *field;
+                Outcome::Continue
+            }
+        }
+    }
+
+    fn move_focus(&mut self, delta: isize) {
+        let at = FIELDS.iter().position(|f| *f == self.focus).unwrap_or(0) as isize;
+        self.focus = FIELDS[(at + delta).rem_euclid(FIELDS.len() as isize) as usize];
+    }
+
+    /// The single-line text field under focus, if any
+    fn text_field(&mut self) -> Option<&mut String> {
+        match self.focus {
+            Field::Title => Some(&mut self.title),
+            Field::DueDate => Some(&mut self.due_date),
+            Field::Time => Some(&mut self.time),
+            Field::Estimate => Some(&mut self.estimate),
+            Field::Tags => Some(&mut self.tags),
+            _ => None,
+        }
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        if self.focus == Field::Description {
+            self.description.insert(text);
+        } else if let Some(field) = self.text_field() {
+            // Single-line fields take pasted line breaks as spaces
+            field.push_str(&text.replace(['\r', '\n'], " "));
+        }
+    }
+
+    pub fn handle_key(&mut self, key: &KeyEvent, today: NaiveDate) -> Outcome {
+        let outcome = self.apply_key(key, today);
+        if self.validating {
+            self.problems = self.check(today);
+        }
+        outcome
+    }
+
+    pub fn handle_mouse(&mut self, mouse: &MouseEvent, today: NaiveDate) -> Outcome {
+        let outcome = self.apply_mouse(mouse, today);
+        if self.validating {
+            self.problems = self.check(today);
+        }
+        outcome
+    }
+
+    fn pick_parent(&mut self, index: usize) {
+        if let Some((id, _)) = self.parent_picker.matches().get(index) {
+            self.parent = *id;
+        }
+        self.parent_picker.set_query(String::new());
+        self.move_focus(1);
+    }
+
+    /// Clicks focus a field, pick a choice, parent or day, or press a
+    /// button; the wheel steps through choices and the parent matches
+    fn apply_mouse(&mut self, mouse: &MouseEvent, today: NaiveDate) -> Outcome {
+        let (column, row) = (mouse.column, mouse.row);
+        let inside = |area: &Rect| {
+            (area.x..area.right()).contains(&column) && (area.y..area.bottom()).contains(&row)
+        };
+        let click = mouse.kind == MouseEventKind::Down(MouseButton::Left);
+        let delta = match mouse.kind {
+            MouseEventKind::ScrollUp => -1,
+            MouseEventKind::ScrollDown => 1,
+            _ => 0,
+        };
+
+        // Popups cover the fields below them
+        if let Some((area, offset)) = self.parent_list.filter(|(area, _)| inside(area)) {
+            if click {
+                self.pick_parent(offset + (row - area.y) as usize);
+            } else if delta != 0 {
+                self.parent_picker.step(delta);
+            }
+            return Outcome::Continue;
+        }
+        if let Some((area, first)) = self.calendar.filter(|(area, _)| inside(area)) {
+            // Below the weekday header, three columns per day
+            if click && row > area.y {
+                let day = (row - area.y - 1) as i64 * 7 + ((column - area.x) / 3) as i64
+                    - first.weekday().num_days_from_monday() as i64;
+                if let Some(date) = first
+                    .checked_add_signed(chrono::Duration::days(day))
+                    .filter(|date| day >= 0 && date.month() == first.month())
+                {
+                    self.due_date = date.format("%Y-%m-%d").to_string();
+                }
+            }
+            return Outcome::Continue;
+        }
+
+        let Some((field, area)) = self.areas.iter().copied().find(|(_, area)| inside(area)) else {
+            return Outcome::Continue;
+        };
+        let inner = area.inner(&Margin::new(1, 1));
+        if click {
+            self.focus = field;
+            match field {
+                Field::Save => return self.save(today),
+                Field::Cancel => return Outcome::Cancel,
+                Field::Description if inside(&inner) => self
+                    .description
+                    .click((column - inner.x) as usize, (row - inner.y) as usize),
+                _ => {}
+            }
+        }
+        let choice = match field {
+            Field::Category => &mut self.category,
+            Field::Priority => &mut self.priority,
+            Field::Status => &mut self.status,
+            Field::Description if delta != 0 => {
+                self.description.scroll(delta);
+                return Outcome::Continue;
+            }
+            _ => return Outcome::Continue,
+        };
+        if click && inside(&inner) {
+            if let Some(i) = choice.option_at(column - inner.x) {
+                choice.selected = i;
+            }
+        } else if delta != 0 {
+            choice.step(delta);
+        }
+        Outcome::Continue
+    }
+
+    fn apply_key(&mut self, key: &KeyEvent, today: NaiveDate) -> Outcome {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Outcome::Cancel,
+            KeyCode::Char('s') if ctrl => return self.save(today),
+            KeyCode::Tab => {
+                self.move_focus(1);
+                return Outcome::Continue;
+            }
+            KeyCode::BackTab => {
+                self.move_focus(-1);
+                return Outcome::Continue;
+            }
+            _ => {}
+        }
+
+        match self.focus {
+            Field::Description => {
+                if ctrl && key.code == KeyCode::Char('e') {
+                    return Outcome::OpenEditor;
+                }
+                self.description.handle_key(key);
+            }
+            Field::Category | Field::Priority | Field::Status => {
+                let choice = match self.focus {
+                    Field::Category => &mut self.category,
+                    Field::Priority => &mut self.priority,
+                    _ => &mut self.status,
+                };
+                match key.code {
+                    KeyCode::Left | KeyCode::Up => choice.step(-1),
+                    KeyCode::Right | KeyCode::Down => choice.step(1),
+                    KeyCode::Enter => self.move_focus(1),
+                    _ => {}
+                }
+            }
+            Field::DueDate if !ctrl => match key.code {
+                KeyCode::Left => self.shift_due_date(today, |d| d.pred_opt()),
+                KeyCode::Right => self.shift_due_date(today, |d| d.succ_opt()),
+                KeyCode::Up => {
+                    self.shift_due_date(today, |d| d.checked_sub_days(chrono::Days::new(7)))
+                }
+                KeyCode::Down => {
+                    self.shift_due_date(today, |d| d.checked_add_days(chrono::Days::new(7)))
+                }
+                KeyCode::PageUp => {
+                    self.shift_due_date(today, |d| d.checked_sub_months(Months::new(1)))
+                }
+                KeyCode::PageDown => {
+                    self.shift_due_date(today, |d| d.checked_add_months(Months::new(1)))
+                }
+                KeyCode::Delete => self.due_date.clear(),
+                KeyCode::Enter => self.move_focus(1),
+                KeyCode::Backspace => {
+                    self.due_date.pop();
+                }
+                KeyCode::Char(c) => self.due_date.push(c),
+                _ => {}
+            },
+            Field::Save | Field::Cancel => match key.code {
+                KeyCode::Left | KeyCode::Right => {
+                    self.focus = if self.focus == Field::Save {
+                        Field::Cancel
+                    } else {
+                        Field::Save
+                    }
+                }
+                KeyCode::Enter if self.focus == Field::Save => return self.save(today),
+                KeyCode::Enter => return Outcome::Cancel,
+                _ => {}
+            },
+            Field::ParentTask => {
+                let picker = &mut self.parent_picker;
+                match key.code {
+                    KeyCode::Up => picker.step(-1),
+                    KeyCode::Down => picker.step(1),
+                    KeyCode::Enter => {
+                        let selected = picker.selected;
+                        self.pick_parent(selected);
+                    }
+                    KeyCode::Delete => {
+                        self.parent = None;
+                        picker.set_query(String::new());
+                    }
+                    KeyCode::Backspace => {
+                        let mut query = picker.query.clone();
+                        query.pop();
+                        picker.set_query(query);
+                    }
+                    KeyCode::Char(c) if !ctrl => {
+                        let query = format!("{}{}", picker.query, c);
+                        picker.set_query(query);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                let field = match self.text_field() {
+                    Some(field) => field,
+                    None => return Outcome::Continue,
+                };
+                match key.code {
+                    KeyCode::Backspace => {
+                        field.pop();
+                    }
+                    KeyCode::Char(c) if !ctrl => field.push(c),
+                    KeyCode::Enter => self.move_focus(1),
+                    _ => {}
+                }
+            }
+        }
+        Outcome::Continue
+    }
+
+    fn block(&self, field: Field, label: &str, theme: &Theme) -> Block<'static> {
+        let title = match self.problem(field) {
+            Some(problem) => Span::styled(format!("{} - {}", label, problem), theme.overdue()),
+            None => Span::raw(label.to_string()),
+        };
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(theme.border(self.focus == field))
+    }
+
+    fn render_text(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        (field, label): (Field, &str),
+        text: &str,
+        theme: &Theme,
+    ) {
+        let block = self.block(field, label, theme);
+        let inner = block.inner(area);
+        f.render_widget(
+            Paragraph::new(text.to_string())
+                .style(theme.text())
+                .block(block),
+            area,
+        );
+        if self.focus == field {
+            let width = text.chars().count() as u16;
+            f.set_cursor(inner.x + width.min(inner.width.saturating_sub(1)), inner.y);
+        }
+    }
+
+    fn render_choice(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        (field, label): (Field, &str),
+        choice: &Choice,
+        theme: &Theme,
+    ) {
+        let tabs = Tabs::new(choice.options.clone())
+            .select(choice.selected)
+            .style(theme.text())
+            .highlight_style(if self.focus == field {
+                theme.selection()
+            } else {
+                theme
+                    .text()
+                    .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            })
+            .block(self.block(field, label, theme));
+        f.render_widget(tabs, area);
+    }
+
+    /// Month view next to the due date field, with the picked day
+    /// highlighted. Returns its inside and the first of the month.
+    fn render_calendar(
+        &self,
+        f: &mut Frame,
+        field: Rect,
+        theme: &Theme,
+        today: NaiveDate,
+    ) -> (Rect, NaiveDate) {
+        let picked = self.due_date(today).ok().flatten();
+        let shown = picked.unwrap_or(today);
+        let first = shown.with_day(1).unwrap_or(shown);
+        let next_month = first.checked_add_months(Months::new(1)).unwrap_or(first);
+        let days = (next_month - first).num_days() as u32;
+        let offset = first.weekday().num_days_from_monday();
+
+        let mut lines = vec![Line::from(Span::styled(
+            "Mo Tu We Th Fr Sa Su",
+            theme.text().add_modifier(Modifier::BOLD),
+        ))];
+        let mut week: Vec<Span> = vec![Span::raw("   ".repeat(offset as usize))];
+        for day in 1..=days {
+            let date = first.with_day(day).unwrap_or(first);
+            let style = if Some(date) == picked {
+                theme.selection()
+            } else if date == today {
+                theme.text().add_modifier(Modifier::UNDERLINED)
+            } else {
+                theme.text()
+            };
+            week.push(Span::styled(format!("{:>2}", day), style));
+            if date.weekday().num_days_from_monday() == 6 || day == days {
+                lines.push(Line::from(std::mem::take(&mut week)));
+            } else {
+                week.push(Span::raw(" "));
+            }
+        }
+
+        let frame = f.size();
+        let width = 22u16.min(frame.width);
+        let height = (lines.len() as u16 + 2).min(frame.height);
+        let area = Rect {
+            x: field.right().saturating_sub(width + 1),
+            y: (field.y + field.height).min(frame.height.saturating_sub(height)),
+            width,
+            height,
+        };
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .title(first.format("%B %Y").to_string())
+                    .borders(Borders::ALL)
+                    .border_style(theme.border(true)),
+            ),
+            area,
+        );
+        (area.inner(&Margin::new(1, 1)), first)
+    }
+
+    /// Matches listed under the parent task field. Returns the list's inside
+    /// and scroll offset.
+    fn render_parent_matches(&self, f: &mut Frame, field: Rect, theme: &Theme) -> (Rect, usize) {
+        let matches = self.parent_picker.matches();
+        let frame = f.size();
+        let top = field.y + field.height;
+        let height = (matches.len() as u16 + 2)
+            .min(10)
+            .min(frame.height.saturating_sub(top));
+        let area = Rect {
+            x: field.x + 1,
+            y: top,
+            width: field.width.saturating_sub(2),
+            height,
+        };
+        let items: Vec<ListItem> = matches
+            .iter()
+            .map(|(id, path)| {
+                let style = if *id == self.parent {
+                    theme.text().add_modifier(Modifier::BOLD)
+                } else {
+                    theme.text()
+                };
+                ListItem::new(Span::styled(path.to_string(), style))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(format!("{} match(es)", matches.len() - 1))
+                    .borders(Borders::ALL)
+                    .border_style(theme.border(true)),
+            )
+            .highlight_style(theme.selection());
+        let mut state = ListState::default().with_selected(Some(self.parent_picker.selected));
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(list, area, &mut state);
+        (area.inner(&Margin::new(1, 1)), state.offset())
+    }
+
+    fn hint(&self) -> &'static str {
+        match self.focus {
+            Field::Description => "Enter: new line - Shift+arrows: select - Ctrl+C/X/V: copy, cut, paste - Ctrl+E: open in $EDITOR",
+            Field::Category | Field::Priority | Field::Status => "Left/Right: choose",
+            Field::DueDate => "Left/Right: day - Up/Down: week - PgUp/PgDn: month - Del: clear - or type YYYY-MM-DD, tomorrow, eow, +3d",
+            Field::Time => "HH:MM, 24-hour",
+            Field::Estimate => "Expected effort in hours or points, e.g. 1.5",
+            Field::Tags => "Space-separated, e.g. home urgent",
+            Field::ParentTask => {
+                "Type to search - Up/Down: choose - Enter: set - Del: move to the top level"
+            }
+            _ => "Enter: next field",
+        }
+    }
+
+    pub fn render(&mut self, f: &mut Frame, theme: &Theme, today: NaiveDate) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(3), // Title
+                    Constraint::Min(5),    // Description
+                    Constraint::Length(3), // Category
+                    Constraint::Length(3), // Parent Task
+                    Constraint::Length(3), // Due Date
+                    Constraint::Length(3), // Time and Estimate
+                    Constraint::Length(3), // Priority
+                    Constraint::Length(3), // Status
+                    Constraint::Length(3), // Tags
+                    Constraint::Length(3), // Save / Cancel
+                    Constraint::Length(1), // Hint
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+
+        self.render_text(f, chunks[0], (Field::Title, "Title"), &self.title, theme);
+
+        let focused = self.focus == Field::Description;
+        let description_block = Block::default()
+            .title(match &self.editor_error {
+                Some(e) => Span::styled(format!("Description - {}", e), theme.overdue()),
+                None => Span::raw("Description"),
+            })
+            .borders(Borders::ALL)
+            .border_style(theme.border(focused));
+        self.description
+            .render(f, chunks[1], description_block, focused, theme);
+
+        self.render_choice(
+            f,
+            chunks[2],
+            (Field::Category, "Category"),
+            &self.category,
+            theme,
+        );
+        // The query while searching, else the current parent
+        let parent = match self.parent {
+            _ if !self.parent_picker.query.is_empty() => self.parent_picker.query.clone(),
+            Some(parent) => match self.parent_picker.path(parent) {
+                Some(path) => path.to_string(),
+                None => format!("Task {}", parent),
+            },
+            None => "None (top level)".to_string(),
+        };
+        self.render_text(
+            f,
+            chunks[3],
+            (Field::ParentTask, "Parent Task"),
+            &parent,
+            theme,
+        );
+        self.render_text(
+            f,
+            chunks[4],
+            (Field::DueDate, "Due Date"),
+            &self.due_date,
+            theme,
+        );
+        let time_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[5]);
+        self.render_text(f, time_chunks[0], (Field::Time, "Time"), &self.time, theme);
+        self.render_text(
+            f,
+            time_chunks[1],
+            (Field::Estimate, "Estimate"),
+            &self.estimate,
+            theme,
+        );
+        self.render_choice(
+            f,
+            chunks[6],
+            (Field::Priority, "Priority"),
+            &self.priority,
+            theme,
+        );
+        self.render_choice(f, chunks[7], (Field::Status, "Status"), &self.status, theme);
+        self.render_text(f, chunks[8], (Field::Tags, "Tags"), &self.tags, theme);
+
+        let buttons = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[9]);
+        let problems = self.problems.len();
+        for (field, label, area) in [
+            (Field::Save, "Save (Ctrl+S)", buttons[0]),
+            (Field::Cancel, "Cancel (Esc)", buttons[1]),
+        ] {
+            let focused = self.focus == field;
+            let title = match (field, problems) {
+                (Field::Save, n) if n > 0 => {
+                    Span::styled(format!("{} problem(s) to fix", n), theme.overdue())
+                }
+                _ => Span::raw(""),
+            };
+            f.render_widget(
+                Paragraph::new(label)
+                    .alignment(Alignment::Center)
+                    .style(if focused {
+                        theme.selection()
+                    } else {
+                        theme.text()
+                    })
+                    .block(
+                        Block::default()
+                            .title(title)
+                            .borders(Borders::ALL)
+                            .border_style(theme.border(focused)),
+                    ),
+                area,
+            );
+        }
+
+        f.render_widget(
+            Paragraph::new(format!(
+                "Tab/Shift+Tab: next/previous field - Ctrl+S: save - Esc: cancel - {}",
+                self.hint()
+            ))
+            .style(theme.text()),
+            chunks[10],
+        );
+
+        self.areas = vec![
+            (Field::Title, chunks[0]),
+            (Field::Description, chunks[1]),
+            (Field::Category, chunks[2]),
+            (Field::ParentTask, chunks[3]),
+            (Field::DueDate, chunks[4]),
+            (Field::Time, time_chunks[0]),
+            (Field::Estimate, time_chunks[1]),
+            (Field::Priority, chunks[6]),
+            (Field::Status, chunks[7]),
+            (Field::Tags, chunks[8]),
+            (Field::Save, buttons[0]),
+            (Field::Cancel, buttons[1]),
+        ];
+        self.calendar = (self.focus == Field::DueDate)
+            .then(|| self.render_calendar(f, chunks[4], theme, today));
+        self.parent_list = (self.focus == Field::ParentTask)
+            .then(|| self.render_parent_matches(f, chunks[3], theme));
+    }
+}
@@ -0,0 +1,209 @@
+//! `link-commits`: links tasks to the commits of a local git repository
+//! whose messages mention `task #<id>` or `closes #<id>`.
+
+use crate::db::{CommitLink, Database};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Field and record separators in the `git log` output
+const FIELD: char = '\x1f';
+const RECORD: char = '\x1e';
+
+/// Task ids a commit message refers to, and whether it closes them. "Closes
+/// task #12" closes as well.
+fn references(message: &str) -> BTreeMap<i32, bool> {
+    let words: Vec<&str> = message.split_whitespace().collect();
+    let mut references = BTreeMap::new();
+    for (i, word) in words.iter().enumerate() {
+        let keyword = word
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        let closes = match keyword.as_str() {
+            "closes" => true,
+            "task" => false,
+            _ => continue,
+        };
+        let mut next = words.get(i + 1).copied().unwrap_or_default();
+        if closes && next.eq_ignore_ascii_case("task") {
+            next = words.get(i + 2).copied().unwrap_or_default();
+        }
+        let Some(number) = next.strip_prefix('#') else {
+            continue;
+        };
+        let digits: String = number.chars().take_while(char::is_ascii_digit).collect();
+        if let Ok(id) = digits.parse() {
+            *references.entry(id).or_default() |= closes;
+        }
+    }
+    references
+}
+
+/// The links of every commit on the repository's local branches
+fn read_log(repo: &Path) -> Result<Vec<CommitLink>, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args([
+            "log",
+            "--branches",
+            "--source",
+            "--format=%H%x1f%S%x1f%an%x1f%aI%x1f%s%x1f%b%x1e",
+        ])
+        .output()
+        .map_err(|e| format!("can't run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git log in {} failed: {}",
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The links in `git log` output with the fields of `read_log`'s format
+fn parse_log(log: &str) -> Vec<CommitLink> {
+    let mut links = Vec::new();
+    for record in log.split(RECORD) {
+        let fields: Vec<&str> = record.trim_start().splitn(6, FIELD).collect();
+        let [hash, branch, author, date, summary, body] = fields[..] else {
+            continue;
+        };
+        let branch = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+        for (task_id, closes) in references(&format!("{}\n{}", summary, body)) {
+            links.push(CommitLink {
+                task_id,
+                hash: hash.to_string(),
+                branch: (!branch.is_empty()).then(|| branch.to_string()),
+                summary: summary.to_string(),
+                author: author.to_string(),
+                committed_at: date.to_string(),
+                closes,
+            });
+        }
+    }
+    links
+}
+
+/// Record the links not seen before and, with `complete`, complete the
+/// tasks they close
+pub fn link_commits(
+    db: &Database,
+    repo: &Path,
+    complete: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let links = read_log(repo)?;
+    let mut added = 0;
+    let mut completed = Vec::new();
+    let mut problems = Vec::new();
+    db.transaction(|db| -> Result<(), Box<dyn std::error::Error>> {
+        for link in &links {
+            let short = &link.hash[..link.hash.len().min(7)];
+            let Some(task) = db.get_task(link.task_id)? else {
+                problems.push(format!(
+                    "{} refers to task #{}, which doesn't exist",
+                    short, link.task_id
+                ));
+                continue;
+            };
+            if !db.add_commit_link(link)? {
+                continue;
+            }
+            added += 1;
+            let status = db.workflow().completed();
+            if !complete || !link.closes || db.workflow().is_done(&task.status) {
+                continue;
+            }
+            match db.workflow().check(&task.status, status) {
+                Ok(()) => {
+                    db.set_status(task.id, status)?;
+                    completed.push(format!("Completed task {} \"{}\"", task.id, task.title));
+                }
+                Err(problem) => problems.push(format!(
+                    "{} closes task {} \"{}\", but {}",
+                    short, task.id, task.title, problem
+                )),
+            }
+        }
+        Ok(())
+    })?;
+
+    println!(
+        "Linked {} new commit reference(s) from {}",
+        added,
+        repo.display()
+    );
+    for line in completed.iter().chain(&problems) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references(message: &str) -> Vec<(i32, bool)> {
+        super::references(message).into_iter().collect()
+    }
+
+    #[test]
+    fn finds_task_and_closes_references() {
+        assert_eq!(references("Start login page, task #12"), [(12, false)]);
+        assert_eq!(references("Fix redirect\n\nCloses #7."), [(7, true)]);
+        assert_eq!(references("closes task #7"), [(7, true)]);
+        assert_eq!(
+            references("(Task #3) and TASK #4"),
+            [(3, false), (4, false)]
+        );
+        assert_eq!(
+            references("task #5, closes #5 and task #6"),
+            [(5, true), (6, false)]
+        );
+    }
+
+    #[test]
+    fn ignores_other_mentions() {
+        assert!(references("Fix #12").is_empty());
+        assert!(references("task 12").is_empty());
+        assert!(references("task #").is_empty());
+        assert!(references("task #abc").is_empty());
+        assert!(references("multitask #3").is_empty());
+        assert!(references("closes").is_empty());
+        assert!(references("task #99999999999").is_empty());
+    }
+
+    #[test]
+    fn parses_log_records() {
+        let log = "aaaaaaa1\x1fmain\x1fAda\x1f2026-10-18T10:00:00+02:00\x1fStart, task #1\x1f\x1e\n\
+            bbbbbbb2\x1frefs/heads/feature\x1fBo\x1f2026-10-18T11:00:00+02:00\x1fFix\x1fCloses #2\nSee task #1\n\x1e\n\
+            ccccccc3\x1f\x1fCy\x1f2026-10-18T12:00:00+02:00\x1fUnrelated\x1f\x1e\n";
+        let links = parse_log(log);
+        let found: Vec<(i32, &str, Option<&str>, &str, bool)> = links
+            .iter()
+            .map(|link| {
+                (
+                    link.task_id,
+                    link.hash.as_str(),
+                    link.branch.as_deref(),
+                    link.summary.as_str(),
+                    link.closes,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (1, "aaaaaaa1", Some("main"), "Start, task #1", false),
+                (1, "bbbbbbb2", Some("feature"), "Fix", false),
+                (2, "bbbbbbb2", Some("feature"), "Fix", true),
+            ]
+        );
+        assert_eq!(links[0].author, "Ada");
+        assert_eq!(links[0].committed_at, "2026-10-18T10:00:00+02:00");
+        assert!(parse_log("").is_empty());
+        assert!(parse_log("truncated\x1frecord").is_empty());
+    }
+}
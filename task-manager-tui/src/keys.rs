@@ -0,0 +1,451 @@
+use crate::config::KeySpec;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Everything the main screen can be asked to do from the keyboard
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Add,
+    Edit,
+    Complete,
+    Delete,
+    Search,
+    SwitchView,
+    SwitchTheme,
+    SwitchProfile,
+    NextSavedView,
+    PrevSavedView,
+    SaveView,
+    DeleteView,
+    CycleSort,
+    ToggleSelect,
+    SelectRange,
+    SelectAll,
+    BulkEdit,
+    Undo,
+    Archive,
+    Restore,
+    Attach,
+    OpenAttachment,
+    Planning,
+    FromTemplate,
+    QuickAdd,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 26] = [
+        Action::Quit,
+        Action::Add,
+        Action::QuickAdd,
+        Action::Edit,
+        Action::Complete,
+        Action::Delete,
+        Action::Search,
+        Action::SwitchView,
+        Action::SwitchTheme,
+        Action::SwitchProfile,
+        Action::NextSavedView,
+        Action::PrevSavedView,
+        Action::SaveView,
+        Action::DeleteView,
+        Action::CycleSort,
+        Action::ToggleSelect,
+        Action::SelectRange,
+        Action::SelectAll,
+        Action::BulkEdit,
+        Action::Undo,
+        Action::Archive,
+        Action::Restore,
+        Action::Attach,
+        Action::OpenAttachment,
+        Action::Planning,
+        Action::FromTemplate,
+    ];
+
+    /// Name used in the `[keys]` table of config.toml
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Add => "add",
+            Action::Edit => "edit",
+            Action::Complete => "complete",
+            Action::Delete => "delete",
+            Action::Search => "search",
+            Action::SwitchView => "switch_view",
+            Action::SwitchTheme => "switch_theme",
+            Action::SwitchProfile => "switch_profile",
+            Action::NextSavedView => "next_saved_view",
+            Action::PrevSavedView => "prev_saved_view",
+            Action::SaveView => "save_view",
+            Action::DeleteView => "delete_view",
+            Action::CycleSort => "cycle_sort",
+            Action::ToggleSelect => "toggle_select",
+            Action::SelectRange => "select_range",
+            Action::SelectAll => "select_all",
+            Action::BulkEdit => "bulk_edit",
+            Action::Undo => "undo",
+            Action::Archive => "archive",
+            Action::Restore => "restore",
+            Action::Attach => "attach",
+            Action::OpenAttachment => "open_attachment",
+            Action::Planning => "planning",
+            Action::FromTemplate => "from_template",
+            Action::QuickAdd => "quick_add",
+            Action::Quit => "quit",
+        }
+    }
+
+    /// Label shown in the Help row
+    fn label(self) -> &'static str {
+        match self {
+            Action::Add => "Add Task",
+            Action::Edit => "Edit Task",
+            Action::Complete => "Mark as Completed",
+            Action::Delete => "Delete Task (to Trash)",
+            Action::Search => "Search",
+            Action::SwitchView => "Switch Pane",
+            Action::SwitchTheme => "Next Theme",
+            Action::SwitchProfile => "Next Profile",
+            Action::NextSavedView => "Next Saved View",
+            Action::PrevSavedView => "Previous Saved View",
+            Action::SaveView => "Save Search as View",
+            Action::DeleteView => "Delete Saved View",
+            Action::CycleSort => "Change Sort",
+            Action::ToggleSelect => "Select Task",
+            Action::SelectRange => "Select Range",
+            Action::SelectAll => "Select All",
+            Action::BulkEdit => "Edit Selected",
+            Action::Undo => "Undo",
+            Action::Archive => "Archive",
+            Action::Restore => "Restore",
+            Action::Attach => "Attach Link or File",
+            Action::OpenAttachment => "Open Attachment",
+            Action::Planning => "Planning by Day/Week",
+            Action::FromTemplate => "New from Template",
+            Action::QuickAdd => "Quick Add",
+            Action::Quit => "Quit",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Add => &["ctrl+a"],
+            Action::Edit => &["ctrl+e"],
+            Action::Complete => &["ctrl+c"],
+            Action::Delete => &["ctrl+d"],
+            Action::Search => &["/"],
+            Action::SwitchView => &["tab"],
+            Action::SwitchTheme => &["ctrl+t"],
+            Action::SwitchProfile => &["ctrl+p"],
+            Action::NextSavedView => &["]"],
+            Action::PrevSavedView => &["["],
+            Action::SaveView => &["ctrl+s"],
+            Action::DeleteView => &["ctrl+x"],
+            Action::CycleSort => &["ctrl+o"],
+            Action::ToggleSelect => &["space"],
+            Action::SelectRange => &["v"],
+            Action::SelectAll => &["*"],
+            Action::BulkEdit => &["ctrl+b"],
+            Action::Undo => &["ctrl+z"],
+            Action::Archive => &["A"],
+            Action::Restore => &["R"],
+            Action::Attach => &["ctrl+l"],
+            Action::OpenAttachment => &["o"],
+            Action::Planning => &["ctrl+w"],
+            Action::FromTemplate => &["T"],
+            Action::QuickAdd => &["n"],
+            Action::Quit => &["esc"],
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// A key plus modifiers, parsed from strings like "ctrl+a", "shift+tab" or "f2"
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = spec.split('+').map(str::trim).collect();
+        // A bare "+" splits into two empty strings
+        let (key, mods) = match parts.split_last() {
+            Some((&"", rest)) if rest.last() == Some(&"") => ("+", &rest[..rest.len() - 1]),
+            Some((key, rest)) => (*key, rest),
+            None => return Err(format!("empty key chord '{}'", spec)),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for m in mods {
+            modifiers |= match m.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                other => return Err(format!("unknown modifier '{}' in '{}'", other, spec)),
+            };
+        }
+
+        let code = match key.to_ascii_lowercase().as_str() {
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            f if f.len() > 1 && f.starts_with('f') => match f[1..].parse::<u8>() {
+                Ok(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => return Err(format!("unknown key '{}' in '{}'", key, spec)),
+            },
+            _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next().unwrap()),
+            _ => return Err(format!("unknown key '{}' in '{}'", key, spec)),
+        };
+
+        Ok(Self::normalize(code, modifiers))
+    }
+
+    /// Terminals report Shift+Tab as BackTab and Shift+a as 'A', so fold the
+    /// shift modifier into the key code before comparing chords.
+    fn normalize(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let shifted = modifiers.contains(KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Tab if shifted => KeyCode::BackTab,
+            KeyCode::Char(c) if shifted => KeyCode::Char(c.to_ascii_uppercase()),
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            other => other,
+        };
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeyChord { code, modifiers }
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        *self == Self::normalize(event.code, event.modifiers)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl + ")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt + ")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift + ")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if self.modifiers.is_empty() => write!(f, "{}", c),
+            KeyCode::Char(c) => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::BackTab => write!(f, "Shift + Tab"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Active key bindings for the main screen
+pub struct Keymap {
+    bindings: Vec<(KeyChord, Action)>,
+}
+
+impl Keymap {
+    /// Build the keymap from the `[keys]` config table. Actions missing from
+    /// the table keep their defaults. Unknown actions, unparsable chords and
+    /// chords bound to more than one action are reported as errors.
+    pub fn from_config(keys: &HashMap<String, KeySpec>) -> Result<Self, String> {
+        if let Some(unknown) = keys.keys().find(|name| Action::from_name(name).is_none()) {
+            let known: Vec<&str> = Action::ALL.iter().map(|a| a.name()).collect();
+            return Err(format!(
+                "unknown action '{}' in [keys] (expected one of: {})",
+                unknown,
+                known.join(", ")
+            ));
+        }
+
+        let mut bindings: Vec<(KeyChord, Action)> = Vec::new();
+        for action in Action::ALL {
+            let chords = match keys.get(action.name()) {
+                Some(spec) => spec.chords(),
+                None => action.default_keys().to_vec(),
+            };
+            if chords.is_empty() {
+                return Err(format!("action '{}' has no key bound", action.name()));
+            }
+            for spec in chords {
+                let chord = KeyChord::parse(spec)?;
+                if let Some((_, other)) = bindings.iter().find(|(c, _)| *c == chord) {
+                    return Err(format!(
+                        "key '{}' is bound to both '{}' and '{}'",
+                        chord,
+                        other.name(),
+                        action.name()
+                    ));
+                }
+                bindings.push((chord, action));
+            }
+        }
+
+        Ok(Keymap { bindings })
+    }
+
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(chord, _)| chord.matches(event))
+            .map(|(_, action)| *action)
+    }
+
+    /// Help row text built from the active bindings
+    pub fn help_text(&self) -> String {
+        Action::ALL
+            .iter()
+            .map(|action| {
+                let keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, a)| a == action)
+                    .map(|(chord, _)| chord.to_string())
+                    .collect();
+                format!("{} ({})", keys.join(" / "), action.label())
+            })
+            .collect::<Vec<_>>()
+            .join(" - ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord { code, modifiers }
+    }
+
+    fn keys(bindings: &[(&str, &str)]) -> HashMap<String, KeySpec> {
+        bindings
+            .iter()
+            .map(|(action, spec)| (action.to_string(), KeySpec::One(spec.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn parses_chords() {
+        assert_eq!(
+            KeyChord::parse("ctrl+a"),
+            Ok(chord(KeyCode::Char('a'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            KeyChord::parse("Control + Alt + F2"),
+            Ok(chord(
+                KeyCode::F(2),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+        assert_eq!(
+            KeyChord::parse("space"),
+            Ok(chord(KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyChord::parse("+"),
+            Ok(chord(KeyCode::Char('+'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyChord::parse("ctrl++"),
+            Ok(chord(KeyCode::Char('+'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            KeyChord::parse("PageDown"),
+            Ok(chord(KeyCode::PageDown, KeyModifiers::NONE))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_chords() {
+        assert!(KeyChord::parse("").unwrap_err().contains("unknown key"));
+        assert!(KeyChord::parse("hyper+a")
+            .unwrap_err()
+            .contains("unknown modifier 'hyper'"));
+        assert!(KeyChord::parse("ctrl+nope")
+            .unwrap_err()
+            .contains("unknown key 'nope'"));
+        assert!(KeyChord::parse("f13").unwrap_err().contains("unknown key"));
+        assert!(KeyChord::parse("f0").unwrap_err().contains("unknown key"));
+    }
+
+    #[test]
+    fn folds_shift_into_the_key() {
+        assert_eq!(KeyChord::parse("shift+a"), KeyChord::parse("A"));
+        assert_eq!(KeyChord::parse("shift+tab"), KeyChord::parse("backtab"));
+        assert_eq!(KeyChord::parse("ctrl+A"), KeyChord::parse("ctrl+a"));
+        assert_eq!(
+            KeyChord::parse("shift+up"),
+            Ok(chord(KeyCode::Up, KeyModifiers::SHIFT))
+        );
+        let shift_a = KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT);
+        assert!(KeyChord::parse("A").unwrap().matches(&shift_a));
+        let backtab = KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert!(KeyChord::parse("shift+tab").unwrap().matches(&backtab));
+    }
+
+    #[test]
+    fn maps_defaults_and_overrides() {
+        let keymap = Keymap::from_config(&keys(&[("add", "ctrl+n")])).unwrap();
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(&ctrl('n')), Some(Action::Add));
+        assert_eq!(keymap.action(&ctrl('a')), None);
+        assert_eq!(keymap.action(&ctrl('e')), Some(Action::Edit));
+
+        let mut many = HashMap::new();
+        many.insert(
+            "quit".to_string(),
+            KeySpec::Many(vec!["esc".to_string(), "q".to_string()]),
+        );
+        let keymap = Keymap::from_config(&many).unwrap();
+        let q = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(&q), Some(Action::Quit));
+        assert!(keymap.help_text().contains("Esc / q (Quit)"));
+    }
+
+    #[test]
+    fn reports_config_errors() {
+        let error = |bindings: &[(&str, &str)]| Keymap::from_config(&keys(bindings)).err();
+        assert_eq!(
+            error(&[("edit", "ctrl+a")]).as_deref(),
+            Some("key 'Ctrl + A' is bound to both 'add' and 'edit'")
+        );
+        assert!(error(&[("fly", "f")])
+            .unwrap()
+            .starts_with("unknown action 'fly'"));
+        assert!(error(&[("add", "ctrl+")]).unwrap().contains("unknown key"));
+        let mut empty = HashMap::new();
+        empty.insert("add".to_string(), KeySpec::Many(Vec::new()));
+        assert_eq!(
+            Keymap::from_config(&empty).err().as_deref(),
+            Some("action 'add' has no key bound")
+        );
+    }
+}
@@ -1,45 +1,1314 @@
+mod attachments;
+mod backup;
+mod bulk;
+mod cli;
+mod config;
 mod db; // Ensure the db module is included
+mod encryption;
+mod form;
+mod git;
+mod keys;
+mod merge;
+mod planning;
+mod prompt;
+mod query;
+mod quick_add;
+mod server;
+mod sort;
+mod taskwarrior;
+mod templates;
+mod textarea;
+mod theme;
+mod urgency;
+mod workflow;
 
+use chrono::{Local, NaiveDate};
+use clap::Parser;
 use crossterm::{
-    event::{self, KeyCode, KeyEvent},
+    event::{self, KeyCode},
     execute,
     terminal::{self, ClearType},
 };
+use form::{Outcome, TaskForm};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use keys::{Action, Keymap};
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
-    Terminal,
+    layout::{Constraint, Direction, Layout, Margin, Rect},
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, Tabs, Wrap,
+    },
+    Frame, Terminal,
 };
+use std::collections::{HashMap, HashSet};
 use std::io;
+use std::time::{Duration, Instant};
+use theme::{Theme, Themes};
 
-#[derive(Clone, Copy)]
-enum FocusedField {
-    Title,
-    Description,
-    Category,
-    ParentTask,
-    DueDate,
-    Time,
-    Priority,
-    Status,
+/// Two clicks on the same row within this time make a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Where a list was last drawn, to find the row under the mouse
+#[derive(Default)]
+struct ListArea {
+    /// Inside the list's borders
+    area: Rect,
+    /// First item on screen
+    offset: usize,
+    /// Index of the task or link on each item, None for group headers
+    items: Vec<Option<usize>>,
+}
+
+impl ListArea {
+    fn new(area: Rect, offset: usize, items: Vec<Option<usize>>) -> Self {
+        ListArea {
+            area: area.inner(&Margin::new(1, 1)),
+            offset,
+            items,
+        }
+    }
+
+    fn contains(&self, column: u16, row: u16) -> bool {
+        (self.area.x..self.area.right()).contains(&column)
+            && (self.area.y..self.area.bottom()).contains(&row)
+    }
+}
+
+#[derive(Default)]
+struct ScreenAreas {
+    tasks: ListArea,
+    sub_tasks: ListArea,
+    attachments: ListArea,
+}
+
+impl ScreenAreas {
+    /// The pane at a screen position, and the index of the task or link on
+    /// the row there if any
+    fn at(&self, column: u16, row: u16) -> Option<(Pane, Option<usize>)> {
+        [
+            (Pane::Tasks, &self.tasks),
+            (Pane::SubTasks, &self.sub_tasks),
+            (Pane::Attachments, &self.attachments),
+        ]
+        .into_iter()
+        .find(|(_, list)| list.contains(column, row))
+        .map(|(pane, list)| {
+            let item = list.offset + (row - list.area.y) as usize;
+            (pane, list.items.get(item).copied().flatten())
+        })
+    }
+}
+
+/// Leaves raw mode and releases the mouse and bracketed paste when the TUI
+/// exits, including through an error
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(
+            io::stdout(),
+            event::DisableMouseCapture,
+            event::DisableBracketedPaste
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Which list on the main screen receives navigation and actions
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Tasks,
+    SubTasks,
+    Attachments,
+}
+
+/// What a line typed in place of the search bar is for
+enum PromptKind {
+    /// Name for the current search as a saved view
+    SaveView,
+    /// Changes for the marked tasks, e.g. "pri:high due:+3d"
+    BulkEdit,
+    /// URL or file path to attach to the task with this id
+    Attach(i32),
+    /// Template name, optionally followed by the date its due offsets count
+    /// from, e.g. "release +7d"
+    Template,
+    /// A task on one line, e.g. "Pay rent #home !high @tomorrow"
+    QuickAdd,
+    /// Whether to complete the task with this id now that all of its
+    /// sub-tasks are done; answered with y or n
+    CompleteParent(i32),
+}
+
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+struct App {
+    tasks: Vec<db::Task>,
+    task_state: ListState,
+    subtask_state: ListState,
+    attachment_state: ListState,
+    pane: Pane,
+    /// The list whose selected task the Details and Attachments panes show
+    detail_pane: Pane,
+    search_query: String,
+    searching: bool,
+    /// Ids matching the active view and search, None when both are empty
+    matching_ids: Option<Vec<i32>>,
+    search_error: Option<query::ParseError>,
+    views: Vec<db::SavedView>,
+    /// Selected tab: 0 is "All", n is `views[n - 1]`
+    active_view: usize,
+    /// Live task count per saved view, None when its query doesn't parse
+    view_counts: Vec<Option<usize>>,
+    prompt: Option<Prompt>,
+    urgency: HashMap<i32, f64>,
+    coefficients: config::UrgencyCoefficients,
+    /// Sort specs cycle_sort steps through, validated at startup
+    sort_orders: Vec<String>,
+    /// Index into `sort_orders` picked with cycle_sort, overriding the view
+    sort_choice: Option<usize>,
+    /// Tasks marked for a bulk action
+    marked: HashSet<i32>,
+    /// Where a range selection started, while one is open
+    range_anchor: Option<(Pane, usize)>,
+    undo_stack: Vec<UndoStep>,
+    attachments: HashMap<i32, Vec<db::Attachment>>,
+    /// Commits found by `link-commits`, by task id
+    commits: HashMap<i32, Vec<db::CommitLink>>,
+    templates: Vec<templates::Template>,
+    /// Ids of the tasks each task depends on
+    dependencies: HashMap<i32, Vec<i32>>,
+    rollups: HashMap<i32, planning::Rollup>,
+    planning_config: config::PlanningConfig,
+    backup_config: config::BackupConfig,
+    workflow: workflow::Workflow,
+    work_days: Vec<chrono::Weekday>,
+    /// Shown in place of the task lists while set
+    planning: Option<planning::Period>,
+    /// Outcome of the last action, shown in the Help title
+    message: Option<String>,
+    /// Where the lists were last drawn
+    areas: ScreenAreas,
+    /// Time, pane and row of the last click, to detect double clicks
+    last_click: Option<(Instant, Pane, usize)>,
+}
+
+/// The state of the tasks an action touched, from before it ran
+struct UndoStep {
+    label: String,
+    records: Vec<db::SyncRecord>,
+}
+
+impl App {
+    fn new(db: &db::Database, config: &config::Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut app = App {
+            tasks: Vec::new(),
+            task_state: ListState::default(),
+            subtask_state: ListState::default(),
+            attachment_state: ListState::default(),
+            pane: Pane::Tasks,
+            detail_pane: Pane::Tasks,
+            search_query: String::new(),
+            searching: false,
+            matching_ids: None,
+            search_error: None,
+            views: Vec::new(),
+            active_view: 0,
+            view_counts: Vec::new(),
+            prompt: None,
+            urgency: HashMap::new(),
+            coefficients: config.urgency.clone(),
+            sort_orders: config.sort_orders.clone(),
+            sort_choice: None,
+            marked: HashSet::new(),
+            range_anchor: None,
+            undo_stack: Vec::new(),
+            attachments: HashMap::new(),
+            commits: HashMap::new(),
+            templates: Vec::new(),
+            dependencies: HashMap::new(),
+            rollups: HashMap::new(),
+            planning_config: config.planning.clone(),
+            backup_config: config.backup.clone(),
+            workflow: db.workflow().clone(),
+            work_days: planning::work_days(&config.planning)?,
+            planning: None,
+            message: None,
+            areas: ScreenAreas::default(),
+            last_click: None,
+        };
+        app.reload(db)?;
+        Ok(app)
+    }
+
+    fn reload(&mut self, db: &db::Database) -> Result<(), Box<dyn std::error::Error>> {
+        // Archived and trashed tasks too, for the views that ask for them
+        self.tasks = db.get_tasks(db::Scope::All)?;
+        self.dependencies = db.dependencies()?;
+        self.urgency = urgency::scores(
+            &self.tasks,
+            &self.dependencies,
+            &self.coefficients,
+            &self.workflow,
+            Local::now().date_naive(),
+        );
+        self.attachments = db.attachments()?;
+        self.commits = db.commit_links()?;
+        self.rollups = planning::rollups(&self.tasks, &self.workflow);
+        self.views = db.saved_views()?;
+        self.active_view = self.active_view.min(self.views.len());
+        self.apply_search(db)
+    }
+
+    fn active_view(&self) -> Option<&db::SavedView> {
+        self.active_view
+            .checked_sub(1)
+            .and_then(|i| self.views.get(i))
+    }
+
+    /// Ids of the tasks matching a query: SQL for the structured terms, then
+    /// the fuzzy matcher for bare words
+    fn evaluate(
+        &self,
+        db: &db::Database,
+        query: &query::Query,
+    ) -> Result<Vec<i32>, Box<dyn std::error::Error>> {
+        let (sql, params) = query.to_sql(Local::now().date_naive(), &self.workflow);
+        let ids = db.find_task_ids(&sql, &params)?;
+        let text = query.fuzzy_text();
+        if text.is_empty() {
+            return Ok(ids);
+        }
+        let matcher = SkimMatcherV2::default();
+        Ok(self
+            .tasks
+            .iter()
+            .filter(|task| ids.contains(&task.id))
+            .filter(|task| {
+                matcher.fuzzy_match(&task.title, &text).is_some()
+                    || matcher.fuzzy_match(&task.description, &text).is_some()
+            })
+            .map(|task| task.id)
+            .collect())
+    }
+
+    /// Re-run the active view and search query and refresh the view counts.
+    /// A search that doesn't parse keeps the last results and shows the
+    /// error in the search bar.
+    fn apply_search(&mut self, db: &db::Database) -> Result<(), Box<dyn std::error::Error>> {
+        let mut counts = Vec::new();
+        for view in &self.views {
+            counts.push(match query::parse(&view.query) {
+                Ok(query) => Some(self.evaluate(db, &query)?.len()),
+                Err(_) => None,
+            });
+        }
+        self.view_counts = counts;
+
+        let search = match query::parse(&self.search_query) {
+            Ok(search) => search,
+            Err(e) => {
+                self.search_error = Some(e);
+                return Ok(());
+            }
+        };
+        let mut query = match self.active_view().map(|view| query::parse(&view.query)) {
+            Some(Ok(query)) => query,
+            Some(Err(e)) => {
+                self.search_error = Some(query::ParseError {
+                    column: e.column,
+                    message: format!("saved view: {}", e.message),
+                });
+                return Ok(());
+            }
+            None => query::Query::default(),
+        };
+        query.clauses.extend(search.clauses);
+        self.search_error = None;
+        self.matching_ids = if query.is_empty() {
+            None
+        } else {
+            Some(self.evaluate(db, &query)?)
+        };
+        self.clamp_selection();
+        Ok(())
+    }
+
+    /// Sort in effect: the one picked with cycle_sort, else the active
+    /// view's, else the first configured order
+    fn sort_spec(&self) -> &str {
+        match (self.sort_choice, self.active_view()) {
+            (Some(i), _) => &self.sort_orders[i],
+            (None, Some(view)) if !view.sort.is_empty() => &view.sort,
+            _ => self.sort_orders.first().map_or("", String::as_str),
+        }
+    }
+
+    fn group_by(&self) -> sort::GroupBy {
+        self.active_view()
+            .and_then(|view| sort::GroupBy::parse(&view.group_by).ok())
+            .unwrap_or(sort::GroupBy::None)
+    }
+
+    fn cycle_sort(&mut self) {
+        if self.sort_orders.is_empty() {
+            return;
+        }
+        let current = self.sort_orders.iter().position(|s| s == self.sort_spec());
+        self.sort_choice = Some(current.map_or(0, |i| (i + 1) % self.sort_orders.len()));
+    }
+
+    fn sort_tasks(&self, tasks: &mut [&db::Task]) {
+        let keys = sort::parse_sort(self.sort_spec()).unwrap_or_default();
+        let group = self.group_by();
+        tasks.sort_by(|a, b| {
+            group
+                .compare(a, b, &self.workflow)
+                .then_with(|| sort::compare(a, b, &keys, &self.urgency, &self.workflow))
+        });
+    }
+
+    /// Top-level tasks, or every task matching the view and search, in the
+    /// view's order
+    fn main_tasks(&self) -> Vec<&db::Task> {
+        let mut tasks: Vec<&db::Task> = self
+            .tasks
+            .iter()
+            .filter(|task| match &self.matching_ids {
+                None => task.parent_task_id.is_none() && !task.is_hidden(),
+                Some(ids) => ids.contains(&task.id),
+            })
+            .collect();
+        self.sort_tasks(&mut tasks);
+        tasks
+    }
+
+    /// Save the current view plus search under `name` and switch to it
+    fn save_view(
+        &mut self,
+        db: &db::Database,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (query, sort, group_by) = match self.active_view() {
+            Some(view) => (
+                format!("{} {}", view.query, self.search_query)
+                    .trim()
+                    .to_string(),
+                self.sort_spec().to_string(),
+                view.group_by.clone(),
+            ),
+            None => (
+                self.search_query.trim().to_string(),
+                self.sort_spec().to_string(),
+                String::new(),
+            ),
+        };
+        db.save_view(name, &query, &sort, &group_by)?;
+        self.search_query.clear();
+        self.sort_choice = None;
+        self.views = db.saved_views()?;
+        self.active_view = self
+            .views
+            .iter()
+            .position(|view| view.name == name)
+            .map_or(0, |i| i + 1);
+        self.reload(db)
+    }
+
+    fn switch_view(
+        &mut self,
+        db: &db::Database,
+        delta: isize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let tabs = self.views.len() as isize + 1;
+        self.active_view = (self.active_view as isize + delta).rem_euclid(tabs) as usize;
+        self.sort_choice = None;
+        self.task_state.select(Some(0));
+        self.apply_search(db)
+    }
+
+    /// Direct sub-tasks of the selected main task that are archived or
+    /// trashed along with it
+    fn sub_tasks(&self) -> Vec<&db::Task> {
+        let parent = self
+            .task_state
+            .selected()
+            .and_then(|i| self.main_tasks().get(i).copied());
+        let mut tasks: Vec<&db::Task> = match parent {
+            Some(parent) => self
+                .tasks
+                .iter()
+                .filter(|task| task.parent_task_id == Some(parent.id))
+                .filter(|task| {
+                    task.archived_at.is_some() == parent.archived_at.is_some()
+                        && task.trashed_at.is_some() == parent.trashed_at.is_some()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let keys = sort::parse_sort(self.sort_spec()).unwrap_or_default();
+        tasks.sort_by(|a, b| sort::compare(a, b, &keys, &self.urgency, &self.workflow));
+        tasks
+    }
+
+    /// The task that actions apply to, depending on the focused pane
+    fn selected_task(&self) -> Option<db::Task> {
+        match self.pane {
+            Pane::Tasks => self
+                .task_state
+                .selected()
+                .and_then(|i| self.main_tasks().get(i).map(|task| (*task).clone())),
+            Pane::SubTasks => self
+                .subtask_state
+                .selected()
+                .and_then(|i| self.sub_tasks().get(i).map(|task| (*task).clone())),
+            Pane::Attachments => self.detail_task().cloned(),
+        }
+    }
+
+    /// The task shown in the Details pane: the selected sub-task when the
+    /// sub-task list was focused last, else the selected main task
+    fn detail_task(&self) -> Option<&db::Task> {
+        let sub_task = self
+            .subtask_state
+            .selected()
+            .filter(|_| self.detail_pane == Pane::SubTasks)
+            .and_then(|i| self.sub_tasks().get(i).copied());
+        sub_task.or_else(|| {
+            self.task_state
+                .selected()
+                .and_then(|i| self.main_tasks().get(i).copied())
+        })
+    }
+
+    fn links(&self, task: &db::Task) -> Vec<attachments::Link> {
+        let stored = self
+            .attachments
+            .get(&task.id)
+            .map_or(&[][..], Vec::as_slice);
+        attachments::links(task, stored)
+    }
+
+    fn detail_links(&self) -> Vec<attachments::Link> {
+        self.detail_task()
+            .map(|task| self.links(task))
+            .unwrap_or_default()
+    }
+
+    /// The link under the cursor in the Attachments pane, or the first one
+    /// of the task when another pane is focused
+    fn selected_link(&self) -> Option<attachments::Link> {
+        let index = match self.pane {
+            Pane::Attachments => self.attachment_state.selected()?,
+            _ => 0,
+        };
+        self.detail_links().into_iter().nth(index)
+    }
+
+    fn switch_pane(&mut self) {
+        self.focus_pane(match self.pane {
+            Pane::Tasks => Pane::SubTasks,
+            Pane::SubTasks => Pane::Attachments,
+            Pane::Attachments => Pane::Tasks,
+        });
+    }
+
+    fn focus_pane(&mut self, pane: Pane) {
+        self.pane = pane;
+        if self.pane != Pane::Attachments {
+            self.detail_pane = self.pane;
+        }
+        self.clamp_selection();
+    }
+
+    /// Clicks select a row of the list under the mouse and the wheel moves
+    /// through it. Returns true for a double click on a task, which the
+    /// caller opens for editing; a double click on an attachment opens it.
+    fn handle_mouse(&mut self, mouse: &event::MouseEvent) -> bool {
+        if self.planning.is_some() || self.searching || self.prompt.is_some() {
+            return false;
+        }
+        let Some((pane, row)) = self.areas.at(mouse.column, mouse.row) else {
+            return false;
+        };
+        match mouse.kind {
+            event::MouseEventKind::ScrollUp | event::MouseEventKind::ScrollDown => {
+                self.focus_pane(pane);
+                self.move_selection(if mouse.kind == event::MouseEventKind::ScrollUp {
+                    -1
+                } else {
+                    1
+                });
+            }
+            event::MouseEventKind::Down(event::MouseButton::Left) => {
+                self.focus_pane(pane);
+                let Some(row) = row else {
+                    return false;
+                };
+                self.select_row(row);
+                let double = self.last_click.is_some_and(|(at, last_pane, last_row)| {
+                    (last_pane, last_row) == (pane, row) && at.elapsed() < DOUBLE_CLICK
+                });
+                self.last_click = (!double).then(|| (Instant::now(), pane, row));
+                if double && pane == Pane::Attachments {
+                    if let Some(link) = self.selected_link() {
+                        self.message = attachments::open(&link.target).err();
+                    }
+                } else if double {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn move_selection(&mut self, delta: i32) {
+        let current = match self.pane {
+            Pane::Tasks => &self.task_state,
+            Pane::SubTasks => &self.subtask_state,
+            Pane::Attachments => &self.attachment_state,
+        }
+        .selected()
+        .unwrap_or(0) as i32;
+        self.select_row((current + delta).max(0) as usize);
+    }
+
+    /// Select a row of the focused list, or its last one when `index` is
+    /// past the end
+    fn select_row(&mut self, index: usize) {
+        let (len, state) = match self.pane {
+            Pane::Tasks => (self.main_tasks().len(), &mut self.task_state),
+            Pane::SubTasks => (self.sub_tasks().len(), &mut self.subtask_state),
+            Pane::Attachments => (self.detail_links().len(), &mut self.attachment_state),
+        };
+        if len > 0 {
+            state.select(Some(index.min(len - 1)));
+        }
+        if self.pane == Pane::Tasks {
+            self.subtask_state.select(None);
+        }
+        if self.pane != Pane::Attachments {
+            self.attachment_state.select(None);
+        }
+        self.clamp_selection();
+    }
+
+    /// Tasks in the open range selection
+    fn range_ids(&self) -> Vec<i32> {
+        let (pane, anchor) = match self.range_anchor {
+            Some(anchor) => anchor,
+            None => return Vec::new(),
+        };
+        let (tasks, cursor) = match pane {
+            Pane::Tasks => (self.main_tasks(), self.task_state.selected()),
+            Pane::SubTasks => (self.sub_tasks(), self.subtask_state.selected()),
+            Pane::Attachments => return Vec::new(),
+        };
+        let cursor = cursor.unwrap_or(anchor);
+        let (start, end) = (anchor.min(cursor), anchor.max(cursor));
+        tasks
+            .iter()
+            .skip(start)
+            .take(end + 1 - start)
+            .map(|task| task.id)
+            .collect()
+    }
+
+    fn is_marked(&self, id: i32) -> bool {
+        self.marked.contains(&id) || self.range_ids().contains(&id)
+    }
+
+    /// Ids a bulk action applies to: the marked tasks, or else the task
+    /// under the cursor
+    fn target_ids(&self) -> Vec<i32> {
+        let mut ids: Vec<i32> = self.marked.iter().copied().collect();
+        for id in self.range_ids() {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        if ids.is_empty() {
+            ids.extend(self.selected_task().map(|task| task.id));
+        }
+        ids.sort_unstable();
+        ids
+    }
+
+    fn toggle_mark(&mut self) {
+        if let Some(task) = self.selected_task() {
+            if !self.marked.remove(&task.id) {
+                self.marked.insert(task.id);
+            }
+        }
+    }
+
+    /// Start a range at the cursor, or close the open one and keep it marked
+    fn toggle_range(&mut self) {
+        if self.range_anchor.is_some() {
+            self.marked.extend(self.range_ids());
+            self.range_anchor = None;
+            return;
+        }
+        let cursor = match self.pane {
+            Pane::Tasks => self.task_state.selected(),
+            Pane::SubTasks => self.subtask_state.selected(),
+            Pane::Attachments => None,
+        };
+        self.range_anchor = cursor.map(|i| (self.pane, i));
+    }
+
+    /// Mark every task in the list, or clear the marks if they all are
+    fn mark_all(&mut self) {
+        let ids: Vec<i32> = match self.pane {
+            Pane::Tasks => self.main_tasks(),
+            Pane::SubTasks => self.sub_tasks(),
+            Pane::Attachments => Vec::new(),
+        }
+        .iter()
+        .map(|task| task.id)
+        .collect();
+        self.range_anchor = None;
+        if ids.iter().all(|id| self.marked.contains(id)) {
+            self.marked.clear();
+        } else {
+            self.marked.extend(ids);
+        }
+    }
+
+    /// Run `action` on the target tasks in one transaction, keeping their
+    /// previous state (and their sub-trees', when `subtrees` is set) as one
+    /// undo step
+    fn bulk(
+        &mut self,
+        db: &db::Database,
+        verb: &str,
+        subtrees: bool,
+        action: impl Fn(&db::Database, &db::Task) -> Result<(), Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let ids = self.target_ids();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        if ids.len() > 1 {
+            if let Err(e) = backup::take(db, &self.backup_config, "bulk") {
+                self.message = Some(e.to_string());
+                return Ok(());
+            }
+        }
+        let mut affected = ids.clone();
+        if subtrees {
+            for &id in &ids {
+                affected.extend(db.descendant_ids(id)?);
+            }
+        }
+        let records: Vec<db::SyncRecord> = db
+            .sync_records()?
+            .into_iter()
+            .filter(|record| affected.contains(&record.task.id))
+            .collect();
+        let label = format!("{} {} task(s)", verb, ids.len());
+        let result = db.transaction(|db| -> Result<(), Box<dyn std::error::Error>> {
+            for &id in &ids {
+                // A task may already be gone with a deleted ancestor
+                if let Some(task) = db.get_task(id)? {
+                    action(db, &task)?;
+                }
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => {
+                self.undo_stack.push(UndoStep {
+                    label: label.clone(),
+                    records,
+                });
+                self.message = Some(label);
+                self.marked.clear();
+                self.range_anchor = None;
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        self.reload(db)
+    }
+
+    /// Apply parsed bulk changes to the target tasks, refusing to nest a
+    /// task under itself
+    fn bulk_edit(
+        &mut self,
+        db: &db::Database,
+        changes: &[bulk::Change],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.bulk(db, "edited", false, |db, task| {
+            let mut task = task.clone();
+            for change in changes {
+                change.apply(&mut task);
+            }
+            if let Some(parent) = task.parent_task_id {
+                if parent == task.id || db.descendant_ids(task.id)?.contains(&parent) {
+                    return Err(format!("\"{}\" can't be nested under itself", task.title).into());
+                }
+                if db.get_task(parent)?.is_none() {
+                    return Err(format!("parent task {} does not exist", parent).into());
+                }
+            }
+            db.update_task(&task)?;
+            Ok(())
+        })
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        self.message = None;
+        self.prompt = Some(Prompt {
+            kind,
+            input: String::new(),
+        });
+    }
+
+    /// Act on the prompt's input. Input that can't be used leaves the prompt
+    /// open with the problem in its title.
+    fn submit_prompt(
+        &mut self,
+        db: &db::Database,
+        prompt: Prompt,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let input = prompt.input.trim();
+        let problem = match prompt.kind {
+            PromptKind::SaveView if input.is_empty() => None,
+            PromptKind::SaveView => {
+                self.save_view(db, input)?;
+                None
+            }
+            PromptKind::BulkEdit => {
+                match bulk::parse(input, Local::now().date_naive(), &self.workflow) {
+                    Ok(changes) => {
+                        let ids = self.target_ids();
+                        self.bulk_edit(db, &changes)?;
+                        self.offer_to_complete_parent(&ids);
+                        None
+                    }
+                    Err(e) => Some(e),
+                }
+            }
+            PromptKind::Template => {
+                match templates::parse_request(input, Local::now().date_naive())
+                    .and_then(|(name, start)| Ok((templates::find(&self.templates, name)?, start)))
+                {
+                    Ok((template, start)) => {
+                        let (_, count) = templates::instantiate(db, template, start)?;
+                        self.message = Some(format!(
+                            "created {} task(s) from template {}",
+                            count, template.name
+                        ));
+                        self.reload(db)?;
+                        None
+                    }
+                    Err(e) => Some(e),
+                }
+            }
+            PromptKind::QuickAdd => {
+                match quick_add::parse(input, Local::now().date_naive(), &self.workflow) {
+                    Ok(task) => match quick_add::add(db, &task) {
+                        Ok(id) => {
+                            self.message = Some(format!("added task {}", id));
+                            self.reload(db)?;
+                            None
+                        }
+                        Err(e) => Some(e.to_string()),
+                    },
+                    Err(e) => Some(e),
+                }
+            }
+            PromptKind::CompleteParent(id) => {
+                let records = db
+                    .sync_records()?
+                    .into_iter()
+                    .filter(|record| record.task.id == id)
+                    .collect();
+                if let Err(e) = db.set_status(id, self.workflow.completed()) {
+                    self.message = Some(e.to_string());
+                    return Ok(());
+                }
+                self.undo_stack.push(UndoStep {
+                    label: "completed 1 task(s)".to_string(),
+                    records,
+                });
+                self.reload(db)?;
+                self.offer_to_complete_parent(&[id]);
+                None
+            }
+            PromptKind::Attach(task_id) => match attachments::resolve(input) {
+                Ok(target) => {
+                    db.add_attachment(task_id, &target)?;
+                    self.message = Some(format!("attached {}", target));
+                    self.reload(db)?;
+                    None
+                }
+                Err(e) => Some(e),
+            },
+        };
+        if problem.is_some() {
+            self.message = problem;
+            self.prompt = Some(prompt);
+        }
+        Ok(())
+    }
+
+    /// Once tasks are completed, ask whether to complete a parent whose
+    /// sub-tasks are now all done
+    fn offer_to_complete_parent(&mut self, completed: &[i32]) {
+        let task = |id: i32| self.tasks.iter().find(|task| task.id == id);
+        let parent = completed
+            .iter()
+            .filter_map(|&id| task(id))
+            .filter(|task| self.workflow.is_done(&task.status))
+            .filter_map(|task| task.parent_task_id)
+            .find(|&parent| {
+                task(parent).is_some_and(|parent| !self.workflow.is_done(&parent.status))
+                    && self
+                        .rollups
+                        .get(&parent)
+                        .is_some_and(planning::Rollup::all_done)
+            });
+        if let Some(parent) = parent {
+            self.prompt = Some(Prompt {
+                kind: PromptKind::CompleteParent(parent),
+                input: String::new(),
+            });
+        }
+    }
+
+    /// "done/total" sub-tasks of a parent
+    fn progress(&self, task: &db::Task) -> Option<(usize, usize)> {
+        self.rollups
+            .get(&task.id)
+            .filter(|rollup| rollup.sub_tasks > 0)
+            .map(|rollup| (rollup.sub_tasks_done, rollup.sub_tasks))
+    }
+
+    fn undo(&mut self, db: &db::Database) -> Result<(), Box<dyn std::error::Error>> {
+        self.message = Some(match self.undo_stack.pop() {
+            Some(step) => {
+                db.restore(&step.records)?;
+                format!("undid: {}", step.label)
+            }
+            None => "nothing to undo".to_string(),
+        });
+        self.reload(db)
+    }
+
+    /// Keep both selections inside their lists after the data changes
+    fn clamp_selection(&mut self) {
+        let main_len = self.main_tasks().len();
+        self.task_state
+            .select(match (main_len, self.task_state.selected()) {
+                (0, _) => None,
+                (len, Some(i)) => Some(i.min(len - 1)),
+                (_, None) => Some(0),
+            });
+        let sub_len = self.sub_tasks().len();
+        self.subtask_state
+            .select(match (sub_len, self.subtask_state.selected()) {
+                (0, _) => None,
+                (len, Some(i)) => Some(i.min(len - 1)),
+                (_, None) => Some(0),
+            });
+        let links_len = self.detail_links().len();
+        self.attachment_state
+            .select(match (links_len, self.attachment_state.selected()) {
+                (0, _) => None,
+                (len, Some(i)) => Some(i.min(len - 1)),
+                (_, None) => Some(0),
+            });
+    }
+}
+
+/// Estimates due per day or week against the capacity, drawn over the task
+/// lists
+fn render_planning(f: &mut Frame, area: Rect, app: &App, theme: &Theme, today: NaiveDate) {
+    const BAR_WIDTH: usize = 20;
+    let period = app.planning.unwrap_or(planning::Period::Day);
+    let plan = planning::plan(
+        &app.tasks,
+        period,
+        14,
+        &app.planning_config,
+        &app.work_days,
+        &app.workflow,
+        today,
+    );
+    let unit = app.planning_config.unit.as_str();
+    let amount = |value: f64| planning::format_estimate(value, unit);
+
+    // Bars share one scale so rows can be compared
+    let scale = plan
+        .buckets
+        .iter()
+        .map(|bucket| bucket.load.max(bucket.capacity.unwrap_or(0.0)))
+        .fold(0.0, f64::max);
+    let rows: Vec<Row> = plan
+        .buckets
+        .iter()
+        .filter(|bucket| bucket.capacity.is_some() || bucket.tasks > 0)
+        .map(|bucket| {
+            let capacity = bucket.capacity.unwrap_or(0.0);
+            // Load within capacity, load over it, then free capacity
+            let bar: Vec<Span> = (0..BAR_WIDTH)
+                .map(|i| {
+                    let at = (i as f64 + 0.5) * scale / BAR_WIDTH as f64;
+                    match (at <= bucket.load, at <= capacity) {
+                        (true, true) => Span::styled("█", theme.text()),
+                        (true, false) => Span::styled("█", theme.overdue()),
+                        (false, true) => Span::styled("░", theme.text()),
+                        (false, false) => Span::raw(" "),
+                    }
+                })
+                .collect();
+            let mut notes = Vec::new();
+            if bucket.is_over() {
+                notes.push(format!("over by {}", amount(bucket.load - capacity)));
+            }
+            if bucket.unestimated > 0 {
+                notes.push(format!("{} without estimate", bucket.unestimated));
+            }
+            let row = Row::new(vec![
+                Cell::from(bucket.label.clone()),
+                Cell::from(bucket.tasks.to_string()),
+                Cell::from(amount(bucket.load)),
+                Cell::from(bucket.capacity.map_or("-".to_string(), amount)),
+                Cell::from(Line::from(bar)),
+                Cell::from(notes.join(", ")),
+            ]);
+            if bucket.is_over() || bucket.capacity.is_none() {
+                row.style(theme.overdue())
+            } else {
+                row.style(theme.text())
+            }
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(6),
+            Constraint::Length(9),
+            Constraint::Length(9),
+            Constraint::Length(BAR_WIDTH as u16 + 1),
+            Constraint::Min(10),
+        ],
+    )
+    .header(
+        Row::new(vec!["Due", "Tasks", "Load", "Capacity", "", ""])
+            .style(theme.text().add_modifier(Modifier::BOLD)),
+    )
+    .block(
+        Block::default()
+            .title(format!(
+                "Planning by {} - {} per work day - {} unfinished task(s) without a due date",
+                match period {
+                    planning::Period::Day => "day",
+                    planning::Period::Week => "week",
+                },
+                amount(app.planning_config.daily_capacity),
+                plan.unscheduled
+            ))
+            .borders(Borders::ALL)
+            .border_style(theme.border(true)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+/// One row of a task list. `marked` adds a selection column while any task
+/// is marked for a bulk action; `links` is the number of attachments and
+/// `progress` the done and total sub-tasks.
+fn task_list_item<'a>(
+    task: &'a db::Task,
+    theme: &Theme,
+    today: NaiveDate,
+    marked: Option<bool>,
+    links: usize,
+    progress: Option<(usize, usize)>,
+    workflow: &workflow::Workflow,
+) -> ListItem<'a> {
+    let overdue = task.is_overdue(today, workflow);
+    let mut spans = Vec::new();
+    match marked {
+        Some(true) => spans.push(Span::styled("● ", theme.border(true))),
+        Some(false) => spans.push(Span::raw("  ")),
+        None => {}
+    }
+    spans.extend([
+        Span::styled(
+            format!(" {} ", task.status),
+            theme.status_badge(&task.status, workflow),
+        ),
+        Span::raw(" "),
+        Span::styled(
+            task.title.as_str(),
+            if overdue {
+                theme.overdue()
+            } else {
+                theme.text()
+            },
+        ),
+    ]);
+    if let Some((done, total)) = progress {
+        spans.push(Span::styled(
+            format!(" ({}/{} done)", done, total),
+            theme.border(true),
+        ));
+    }
+    for tag in &task.tags {
+        spans.push(Span::styled(format!(" #{}", tag), theme.text()));
+    }
+    if links > 0 {
+        spans.push(Span::styled(format!(" [@{}]", links), theme.border(true)));
+    }
+    if !task.priority.is_empty() {
+        spans.push(Span::styled(
+            format!(" !{}", task.priority),
+            theme.priority(&task.priority),
+        ));
+    }
+    if let Some(due) = task.due_date.as_deref().filter(|due| !due.is_empty()) {
+        let label = if overdue {
+            format!(" (overdue {})", due)
+        } else {
+            format!(" (due {})", due)
+        };
+        spans.push(Span::styled(
+            label,
+            if overdue {
+                theme.overdue()
+            } else {
+                theme.text()
+            },
+        ));
+    }
+    ListItem::new(Line::from(spans))
+}
+
+/// Archive old completed tasks as configured; returns a note when any were
+fn auto_archive(
+    db: &db::Database,
+    config: &config::Config,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if config.archive_after_days == 0 {
+        return Ok(None);
+    }
+    let archived = db.auto_archive(config.archive_after_days)?;
+    Ok((archived > 0).then(|| format!("archived {} completed task(s)", archived)))
+}
+
+/// Open a profile's database, creating its directory on first use, and
+/// bring its schema up to date after backing it up
+fn open_database(
+    profile: &config::Profile,
+    backups: &config::BackupConfig,
+    workflow: &workflow::Workflow,
+) -> Result<db::Database, Box<dyn std::error::Error>> {
+    if let Some(dir) = profile
+        .path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut db = encryption::open(&profile.path)?;
+    db.set_workflow(workflow.clone());
+    if db.needs_migration()? {
+        backup::take(&db, backups, "migration")?;
+    }
+    db.migrate()?;
+    Ok(db)
+}
+
+fn list_backups(
+    profile: &config::Profile,
+    backups: &config::BackupConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let list = backup::list(backups, &profile.path)?;
+    if list.is_empty() {
+        println!("No backups of {}", profile.path.display());
+    }
+    for backup in list {
+        println!(
+            "{:<18} {:<10} {:>10}",
+            backup.id,
+            backup.reason,
+            backup::format_size(backup.size)
+        );
+    }
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = cli::Cli::parse();
+
+    // Load settings first so a bad config is reported before raw mode
+    let config = config::Config::load()?;
+    // Shell prompts run this constantly, so it skips the TUI's setup
+    if let Some(cli::Command::Prompt { format }) = &cli.command {
+        let workflow = workflow::Workflow::new(config.statuses.clone())
+            .map_err(|e| format!("statuses: {}", e))?;
+        let profile = config.resolve_profile(cli.db.as_deref(), cli.profile.as_deref())?;
+        return prompt::print(
+            &profile.path,
+            &workflow,
+            format.as_deref().unwrap_or(&config.prompt.format),
+            &config.prompt.separator,
+        );
+    }
+    let keymap = Keymap::from_config(&config.keys)?;
+    for order in &config.sort_orders {
+        sort::parse_sort(order).map_err(|e| format!("sort_orders: {}", e))?;
+    }
+    planning::work_days(&config.planning)?;
+    let workflow =
+        workflow::Workflow::new(config.statuses.clone()).map_err(|e| format!("statuses: {}", e))?;
+    let mut state = config::State::load();
+    let themes_dir = config::Config::dir().map(|dir| dir.join("themes"));
+    let templates_dir = config::Config::dir().map(|dir| dir.join("templates"));
+    let templates = templates::load(templates_dir.as_deref())?;
+    let mut themes = Themes::load(
+        themes_dir.as_deref(),
+        state.theme.as_deref().or(config.theme.as_deref()),
+    )?;
+
+    // Initialize the database
+    let mut profiles = config.profiles();
+    let mut profile = config.resolve_profile(cli.db.as_deref(), cli.profile.as_deref())?;
+    if !profiles.contains(&profile) {
+        profiles.insert(0, profile.clone());
+    }
+
+    // These replace the file, so they run before it is opened
+    match cli.command {
+        Some(cli::Command::Encrypt) => return encryption::encrypt(&profile.path),
+        Some(cli::Command::Decrypt) => return encryption::decrypt(&profile.path),
+        Some(cli::Command::Rekey) => return encryption::rekey(&profile.path),
+        Some(cli::Command::Backup {
+            command: cli::BackupCommand::List,
+        }) => return list_backups(&profile, &config.backup),
+        Some(cli::Command::Template {
+            command: cli::TemplateCommand::List,
+        }) => {
+            for template in &templates {
+                println!("{:<20} {}", template.name, template.task.title);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+    let mut db = open_database(&profile, &config.backup, &workflow)?;
+
+    match cli.command {
+        Some(cli::Command::Merge { other }) => {
+            backup::take(&db, &config.backup, "merge")?;
+            return merge::merge(&db, &other);
+        }
+        Some(cli::Command::ImportTaskwarrior { file }) => {
+            backup::take(&db, &config.backup, "import")?;
+            return taskwarrior::import(&db, &file);
+        }
+        Some(cli::Command::Serve { port, token }) => {
+            if config.backup.on_startup {
+                backup::take(&db, &config.backup, "startup")?;
+            }
+            let token = token.or(config.server.token.clone());
+            return server::serve(&db, port.unwrap_or(config.server.port), token.as_deref());
+        }
+        Some(cli::Command::Purge { archived }) => {
+            backup::take(&db, &config.backup, "purge")?;
+            println!("Purged {} task(s)", db.purge(archived)?);
+            return Ok(());
+        }
+        Some(cli::Command::Backup {
+            command: cli::BackupCommand::Restore { id },
+        }) => return backup::restore(&mut db, &config.backup, &id),
+        Some(cli::Command::Add { words, dry_run }) => {
+            let task = quick_add::parse(&words.join(" "), Local::now().date_naive(), &workflow)?;
+            let parent = match task.parent_task_id {
+                Some(id) => db.get_task(id)?,
+                None => None,
+            };
+            println!("{}", quick_add::preview(&task, parent.as_ref()));
+            if !dry_run {
+                println!("Added task {}", quick_add::add(&db, &task)?);
+            }
+            return Ok(());
+        }
+        Some(cli::Command::LinkCommits { repo, complete }) => {
+            let repo = repo
+                .or_else(|| config.git.repo.as_deref().map(config::expand_home))
+                .ok_or("no repository; pass --repo or set repo under [git] in config.toml")?;
+            return git::link_commits(&db, &repo, complete || config.git.complete_on_close);
+        }
+        Some(cli::Command::Template {
+            command: cli::TemplateCommand::New { name, start },
+        }) => {
+            let today = Local::now().date_naive();
+            let start = match start {
+                Some(start) => templates::parse_start(&start, today)?,
+                None => today,
+            };
+            let template = templates::find(&templates, &name)?;
+            let (id, count) = templates::instantiate(&db, template, start)?;
+            println!(
+                "Created {} task(s) from template {}, top task {}",
+                count, template.name, id
+            );
+            return Ok(());
+        }
+        Some(
+            cli::Command::Encrypt
+            | cli::Command::Decrypt
+            | cli::Command::Rekey
+            | cli::Command::Backup { .. }
+            | cli::Command::Template { .. }
+            | cli::Command::Prompt { .. },
+        )
+        | None => {
+            if config.backup.on_startup {
+                backup::take(&db, &config.backup, "startup")?;
+            }
+        }
+    }
+
     // Set up terminal
     terminal::enable_raw_mode()?;
+    let _guard = TerminalGuard;
     let mut stdout = io::stdout();
-    execute!(stdout, terminal::Clear(ClearType::All))?;
+    execute!(
+        stdout,
+        terminal::Clear(ClearType::All),
+        event::EnableBracketedPaste,
+        event::EnableMouseCapture
+    )?;
 
     // Create a terminal
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Initialize the database
-    let db = db::Database::new("tasks.db")?;
+    let archived = auto_archive(&db, &config)?;
+    let mut app = App::new(&db, &config)?;
+    app.templates = templates;
+    app.message = archived;
+    let help_text = keymap.help_text();
 
     // Main event loop
     loop {
+        let theme = themes.current().clone();
+        let today = Local::now().date_naive();
+        let mut areas = ScreenAreas::default();
         terminal.draw(|f| {
             let size = f.size();
 
@@ -57,242 +1326,676 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 )
                 .split(size);
 
-            // Row 1: Search bar
-            let search_block = Block::default().title("Search").borders(Borders::ALL);
+            // Row 1: Search bar, or a prompt typed in its place
+            let (search_title, search_text) = if let Some(prompt) = &app.prompt {
+                let (label, hint) = match prompt.kind {
+                    PromptKind::SaveView => (
+                        "Save view",
+                        "Save view as (Enter to save, Esc to cancel)".to_string(),
+                    ),
+                    PromptKind::BulkEdit => (
+                        "Edit selected",
+                        format!(
+                            "Set on {} task(s): status: pri: cat: due: parent: est: (Enter to apply, Esc to cancel)",
+                            app.target_ids().len()
+                        ),
+                    ),
+                    PromptKind::Attach(_) => (
+                        "Attach",
+                        "Attach URL or file path (Enter to attach, Esc to cancel)".to_string(),
+                    ),
+                    PromptKind::Template => (
+                        "Template",
+                        format!(
+                            "Template ({}) and optional start date like +7d (Enter to create, Esc to cancel)",
+                            app.templates
+                                .iter()
+                                .map(|t| t.name.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    ),
+                    PromptKind::CompleteParent(id) => (
+                        "Complete parent",
+                        format!(
+                            "All sub-tasks of \"{}\" are done. Complete it too? (y/n)",
+                            app.tasks
+                                .iter()
+                                .find(|task| task.id == id)
+                                .map_or("", |task| task.title.as_str())
+                        ),
+                    ),
+                    PromptKind::QuickAdd => (
+                        "Quick add",
+                        "Title #category/tag !priority @due HH:MM ^parent (Enter to add, Esc to cancel)"
+                            .to_string(),
+                    ),
+                };
+                let input = prompt.input.trim();
+                let title = match (&prompt.kind, &app.message) {
+                    // The question stands whatever the last action reported
+                    (PromptKind::CompleteParent(_), _) => Span::raw(hint),
+                    // A live preview of the fields, which also shows any problem
+                    (PromptKind::QuickAdd, _) if !input.is_empty() => {
+                        match quick_add::parse(input, today, &app.workflow) {
+                            Ok(task) => {
+                                let parent = task
+                                    .parent_task_id
+                                    .and_then(|id| app.tasks.iter().find(|t| t.id == id));
+                                Span::raw(format!(
+                                    "Add {} (Enter to add, Esc to cancel)",
+                                    quick_add::preview(&task, parent)
+                                ))
+                            }
+                            Err(e) => Span::styled(format!("{} - {}", label, e), theme.overdue()),
+                        }
+                    }
+                    (_, Some(e)) => Span::styled(format!("{} - {}", label, e), theme.overdue()),
+                    (_, None) => Span::raw(hint),
+                };
+                (title, prompt.input.as_str())
+            } else if let Some(e) = &app.search_error {
+                (
+                    Span::styled(format!("Search - {}", e), theme.overdue()),
+                    app.search_query.as_str(),
+                )
+            } else if app.searching {
+                (
+                    Span::raw("Search (Enter to finish)"),
+                    app.search_query.as_str(),
+                )
+            } else {
+                (Span::raw("Search"), app.search_query.as_str())
+            };
+            let prompting = app.searching || app.prompt.is_some();
+            let search_block = Paragraph::new(search_text).style(theme.text()).block(
+                Block::default()
+                    .title(search_title)
+                    .borders(Borders::ALL)
+                    .border_style(theme.border(prompting)),
+            );
             f.render_widget(search_block, chunks[0]);
 
-            // Row 2: Task list (split into 2 columns)
+            // Row 2: Saved view tabs above the task list
+            let view_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+                .split(chunks[1]);
+            let active = app.tasks.iter().filter(|task| !task.is_hidden()).count();
+            let mut tab_titles = vec![format!("All ({})", active)];
+            for (view, count) in app.views.iter().zip(&app.view_counts) {
+                tab_titles.push(match count {
+                    Some(count) => format!("{} ({})", view.name, count),
+                    None => format!("{} (!)", view.name),
+                });
+            }
+            let tabs = Tabs::new(tab_titles)
+                .select(app.active_view)
+                .style(theme.text())
+                .highlight_style(theme.selection());
+            f.render_widget(tabs, view_chunks[0]);
+
+            // Task list (split into 2 columns)
             let task_chunks = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints(
                     [
                         Constraint::Percentage(50), // Column 1: Main Tasks (50%)
-                        Constraint::Percentage(50), // Column 2: Sub-tasks (50%)
+                        Constraint::Percentage(50), // Column 2: Sub-tasks and details (50%)
                     ]
                     .as_ref(),
                 )
-                .split(chunks[1]);
-
-            let task_block = Block::default().title("Tasks").borders(Borders::ALL);
-            f.render_widget(task_block, task_chunks[0]); // Main Tasks
-
-            let subtask_block = Block::default().title("Sub-tasks").borders(Borders::ALL);
-            f.render_widget(subtask_block, task_chunks[1]); // Sub-tasks
-
-            // Row 3: Help section with hotkeys
-            let help_text = "Esc to quit - Ctrl + A (Add Task) - Ctrl + E (Edit Task) - Ctrl + C (Mark as Completed) - Ctrl + D (Delete Task) - Tab (View Sub Tasks)";
-            let help_block = Paragraph::new(help_text)
-                .block(Block::default().title("Help").borders(Borders::ALL));
-            f.render_widget(help_block, chunks[2]);
-        })?;
-
-        // Handle user input
-        if let Ok(true) = event::poll(std::time::Duration::from_millis(100)) {
-            if let Ok(event::Event::Key(KeyEvent {
-                code, modifiers, ..
-            })) = event::read()
-            {
-                match code {
-                    KeyCode::Char('a') if modifiers == event::KeyModifiers::CONTROL => {
-                        show_task_dialog(&db)?;
-                    }
-                    KeyCode::Esc => {
-                        terminal::disable_raw_mode()?;
-                        return Ok(());
-                    }
-                    _ => {}
-                }
-            }
-        }
-    }
-}
-
-fn show_task_dialog(db: &db::Database) -> std::result::Result<(), Box<dyn std::error::Error>> {
-    let mut title = String::new();
-    let mut description = String::new();
-    let categories = vec!["Personal", "Work", "Development", "Home", "Career Related"];
-    let selected_category = categories[0].to_string();
-    let priorities = vec!["High", "Medium", "Low"];
-    let selected_priority = priorities[0].to_string();
-    let statuses = vec!["To Do", "In Progress", "Completed"];
-    let selected_status = statuses[0].to_string();
-    let mut due_date = String::new();
-    let mut time = String::new();
-    let parent_task_id: Option<i32> = None;
-
-    // Fetch existing tasks for parent task selection
-    let existing_tasks: Vec<db::Task> = Vec::new(); // TODO: Implement get_all_tasks
-    let mut filtered_tasks = existing_tasks.iter().collect::<Vec<_>>(); // Collect references instead of cloning
-    let search_query = String::new();
-
-    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
-    let mut focused_field = FocusedField::Title; // Start with the Title field focused
-
-    loop {
-        terminal.draw(|f| {
-            let size = f.size();
-
-            // Draw the dialog
-            let dialog_chunks = Layout::default()
+                .split(view_chunks[1]);
+            let detail_chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints(
                     [
-                        Constraint::Percentage(10), // Title
-                        Constraint::Percentage(10), // Description
-                        Constraint::Percentage(10), // Category
-                        Constraint::Percentage(10), // Parent Task
-                        Constraint::Percentage(10), // Due Date
-                        Constraint::Percentage(10), // Time
-                        Constraint::Percentage(10), // Priority
-                        Constraint::Percentage(10), // Status
-                        Constraint::Percentage(10), // Submit
+                        Constraint::Percentage(40), // Sub-tasks
+                        Constraint::Percentage(30), // Details
+                        Constraint::Percentage(30), // Attachments
                     ]
                     .as_ref(),
                 )
-                .split(size);
+                .split(task_chunks[1]);
 
-            // Title input
-            let title_block = Block::default().title("Title").borders(Borders::ALL);
-            f.render_widget(title_block, dialog_chunks[0]);
-            f.render_widget(Paragraph::new(title.as_str()), dialog_chunks[0]);
-
-            // Description input
-            let description_block = Block::default().title("Description").borders(Borders::ALL);
-            f.render_widget(description_block, dialog_chunks[1]);
-            f.render_widget(Paragraph::new(description.as_str()), dialog_chunks[1]);
-
-            // Category selection
-            let category_block = Block::default().title("Category").borders(Borders::ALL);
-            f.render_widget(category_block.clone(), dialog_chunks[2]);
-            let category_list: Vec<ListItem> =
-                categories.iter().map(|cat| ListItem::new(*cat)).collect();
-            let category_list_widget = List::new(category_list).block(category_block);
-            f.render_widget(category_list_widget, dialog_chunks[2]);
-
-            // Parent Task selection with fuzzy search
-            let parent_task_block = Block::default().title("Parent Task").borders(Borders::ALL);
-            f.render_widget(parent_task_block.clone(), dialog_chunks[3]);
-            filtered_tasks = existing_tasks
-                .iter()
-                .filter(|task| task.title.contains(&search_query))
+            // Grouped views get a header row before each group, so the
+            // selected task's row is offset by the headers above it
+            let group = app.group_by();
+            let marking = !app.marked.is_empty() || app.range_anchor.is_some();
+            let marked = |task: &db::Task| marking.then(|| app.is_marked(task.id));
+            let mut main_tasks: Vec<ListItem> = Vec::new();
+            let mut task_items = Vec::new();
+            let mut selected_row = None;
+            let mut current_group = None;
+            for (i, task) in app.main_tasks().into_iter().enumerate() {
+                if let Some(label) = group.label(task) {
+                    if current_group.as_ref() != Some(&label) {
+                        main_tasks.push(ListItem::new(Span::styled(
+                            label.clone(),
+                            theme
+                                .text()
+                                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                        )));
+                        task_items.push(None);
+                        current_group = Some(label);
+                    }
+                }
+                task_items.push(Some(i));
+                if app.task_state.selected() == Some(i) {
+                    selected_row = Some(main_tasks.len());
+                }
+                main_tasks.push(task_list_item(
+                    task,
+                    &theme,
+                    today,
+                    marked(task),
+                    app.links(task).len(),
+                    app.progress(task),
+                    &app.workflow,
+                ));
+            }
+            let task_list = List::new(main_tasks)
+                .block(
+                    Block::default()
+                        .title(if marking {
+                            format!(
+                                "Tasks - {} - sort: {} - {} selected",
+                                profile.name,
+                                app.sort_spec(),
+                                app.target_ids().len()
+                            )
+                        } else {
+                            format!("Tasks - {} - sort: {}", profile.name, app.sort_spec())
+                        })
+                        .borders(Borders::ALL)
+                        .border_style(theme.border(app.pane == Pane::Tasks)),
+                )
+                .highlight_style(theme.selection())
+                .highlight_symbol(if app.pane == Pane::Tasks {
+                    ">> "
+                } else {
+                    "   "
+                });
+            let mut task_state = ListState::default().with_selected(selected_row);
+            f.render_stateful_widget(task_list, task_chunks[0], &mut task_state); // Main Tasks
+            areas.tasks = ListArea::new(task_chunks[0], task_state.offset(), task_items);
+
+            let sub_tasks: Vec<ListItem> = app
+                .sub_tasks()
+                .into_iter()
+                .map(|task| {
+                    task_list_item(
+                        task,
+                        &theme,
+                        today,
+                        marked(task),
+                        app.links(task).len(),
+                        app.progress(task),
+                        &app.workflow,
+                    )
+                })
                 .collect();
-            let parent_task_list: Vec<ListItem> = filtered_tasks
-                .iter()
-                .map(|task| ListItem::new(task.title.as_str()))
+            let subtask_list = List::new(sub_tasks)
+                .block(
+                    Block::default()
+                        .title("Sub-tasks")
+                        .borders(Borders::ALL)
+                        .border_style(theme.border(app.pane == Pane::SubTasks)),
+                )
+                .highlight_style(theme.selection())
+                .highlight_symbol(if app.pane == Pane::SubTasks {
+                    ">> "
+                } else {
+                    "   "
+                });
+            let sub_task_count = subtask_list.len();
+            let mut subtask_state = app.subtask_state.clone();
+            f.render_stateful_widget(subtask_list, detail_chunks[0], &mut subtask_state); // Sub-tasks
+            areas.sub_tasks = ListArea::new(
+                detail_chunks[0],
+                subtask_state.offset(),
+                (0..sub_task_count).map(Some).collect(),
+            );
+
+            let details = match app.detail_task() {
+                Some(task) => {
+                    let mut lines = vec![
+                        Line::from(Span::styled(
+                            task.title.as_str(),
+                            theme.text().add_modifier(Modifier::BOLD),
+                        )),
+                        Line::from(format!(
+                            "{} - {} - {}",
+                            task.category, task.priority, task.status
+                        )),
+                    ];
+                    if let Some(due) = &task.due_date {
+                        lines.push(Line::from(format!(
+                            "Due {} {}",
+                            due,
+                            task.time.as_deref().unwrap_or("")
+                        )));
+                    }
+                    if !task.tags.is_empty() {
+                        lines.push(Line::from(format!("#{}", task.tags.join(" #"))));
+                    }
+                    let unit = app.planning_config.unit.as_str();
+                    let rollup = app.rollups.get(&task.id).copied().unwrap_or_default();
+                    let own = task.estimate.unwrap_or(0.0);
+                    if rollup.total > own {
+                        lines.push(Line::from(format!(
+                            "Estimate {} - {} with sub-tasks, {} left",
+                            planning::format_estimate(own, unit),
+                            planning::format_estimate(rollup.total, unit),
+                            planning::format_estimate(rollup.remaining, unit)
+                        )));
+                    } else if let Some(estimate) = task.estimate {
+                        lines.push(Line::from(format!(
+                            "Estimate {}",
+                            planning::format_estimate(estimate, unit)
+                        )));
+                    }
+                    let by_estimate = app.planning_config.progress_by_estimate;
+                    if let Some(progress) = rollup.progress(by_estimate) {
+                        const GAUGE_WIDTH: usize = 20;
+                        let filled = (progress * GAUGE_WIDTH as f64).round() as usize;
+                        let mut gauge = vec![
+                            Span::raw("Progress "),
+                            Span::styled("█".repeat(filled), theme.text()),
+                            Span::styled("░".repeat(GAUGE_WIDTH - filled), theme.border(false)),
+                            Span::raw(format!(
+                                " {:.0}% - {}/{} done",
+                                progress * 100.0,
+                                rollup.sub_tasks_done,
+                                rollup.sub_tasks
+                            )),
+                        ];
+                        if by_estimate && rollup.sub_estimate > 0.0 {
+                            gauge.push(Span::raw(format!(
+                                ", {} of {}",
+                                planning::format_estimate(rollup.sub_estimate_done, unit),
+                                planning::format_estimate(rollup.sub_estimate, unit)
+                            )));
+                        }
+                        lines.push(Line::from(gauge));
+                    }
+                    for id in app.dependencies.get(&task.id).into_iter().flatten() {
+                        if let Some(other) = app.tasks.iter().find(|other| other.id == *id) {
+                            lines.push(Line::from(format!(
+                                "Depends on \"{}\" ({})",
+                                other.title, other.status
+                            )));
+                        }
+                    }
+                    for link in app.commits.get(&task.id).into_iter().flatten() {
+                        lines.push(Line::from(vec![
+                            Span::raw(if link.closes { "Closed by " } else { "Commit " }),
+                            Span::styled(
+                                link.hash[..link.hash.len().min(7)].to_string(),
+                                theme.border(false),
+                            ),
+                            Span::raw(match &link.branch {
+                                Some(branch) => format!(" on {}: {}", branch, link.summary),
+                                None => format!(": {}", link.summary),
+                            }),
+                        ]));
+                    }
+                    lines.push(Line::from(""));
+                    lines.extend(task.description.lines().map(Line::from));
+                    lines
+                }
+                None => Vec::new(),
+            };
+            let details_block = Paragraph::new(details)
+                .style(theme.text())
+                .wrap(Wrap { trim: false })
+                .block(
+                    Block::default()
+                        .title("Details")
+                        .borders(Borders::ALL)
+                        .border_style(theme.border(false)),
+                );
+            f.render_widget(details_block, detail_chunks[1]);
+
+            let links: Vec<ListItem> = app
+                .detail_links()
+                .into_iter()
+                .map(|link| match link.attachment_id {
+                    Some(_) => ListItem::new(Span::styled(link.target, theme.text())),
+                    None => ListItem::new(Line::from(vec![
+                        Span::styled(link.target, theme.text()),
+                        Span::styled(" (from description)", theme.border(false)),
+                    ])),
+                })
                 .collect();
-            let parent_task_list_widget = List::new(parent_task_list).block(parent_task_block);
-            f.render_widget(parent_task_list_widget, dialog_chunks[3]);
-
-            // Due Date input
-            let due_date_block = Block::default().title("Due Date").borders(Borders::ALL);
-            f.render_widget(due_date_block, dialog_chunks[4]);
-            f.render_widget(Paragraph::new(due_date.as_str()), dialog_chunks[4]);
-
-            // Time input
-            let time_block = Block::default().title("Time").borders(Borders::ALL);
-            f.render_widget(time_block, dialog_chunks[5]);
-            f.render_widget(Paragraph::new(time.as_str()), dialog_chunks[5]);
-
-            // Priority selection
-            let priority_block = Block::default().title("Priority").borders(Borders::ALL);
-            f.render_widget(priority_block.clone(), dialog_chunks[6]);
-            let priority_list: Vec<ListItem> =
-                priorities.iter().map(|pri| ListItem::new(*pri)).collect();
-            let priority_list_widget = List::new(priority_list).block(priority_block);
-            f.render_widget(priority_list_widget, dialog_chunks[6]);
-
-            // Status selection
-            let status_block = Block::default().title("Status").borders(Borders::ALL);
-            f.render_widget(status_block.clone(), dialog_chunks[7]);
-            let status_list: Vec<ListItem> =
-                statuses.iter().map(|stat| ListItem::new(*stat)).collect();
-            let status_list_widget = List::new(status_list).block(status_block);
-            f.render_widget(status_list_widget, dialog_chunks[7]);
-
-            // Submit button
-            let submit_block = Block::default().title("Submit").borders(Borders::ALL);
-            f.render_widget(submit_block, dialog_chunks[8]);
+            let link_list = List::new(links)
+                .block(
+                    Block::default()
+                        .title("Attachments")
+                        .borders(Borders::ALL)
+                        .border_style(theme.border(app.pane == Pane::Attachments)),
+                )
+                .highlight_style(theme.selection())
+                .highlight_symbol(if app.pane == Pane::Attachments {
+                    ">> "
+                } else {
+                    "   "
+                });
+            let link_count = link_list.len();
+            let mut attachment_state = app.attachment_state.clone();
+            f.render_stateful_widget(link_list, detail_chunks[2], &mut attachment_state); // Attachments
+            areas.attachments = ListArea::new(
+                detail_chunks[2],
+                attachment_state.offset(),
+                (0..link_count).map(Some).collect(),
+            );
+
+            if app.planning.is_some() {
+                render_planning(f, view_chunks[1], &app, &theme, today);
+            }
+
+            // Row 3: Help section with hotkeys
+            let help_block = Paragraph::new(help_text.as_str())
+                .style(theme.text())
+                .wrap(Wrap { trim: true })
+                .block(
+                    Block::default()
+                        .title(match &app.message {
+                            Some(message) if app.prompt.is_none() => {
+                                format!("Help - theme: {} - {}", theme.name, message)
+                            }
+                            _ => format!("Help - theme: {}", theme.name),
+                        })
+                        .borders(Borders::ALL)
+                        .border_style(theme.border(false)),
+                );
+            f.render_widget(help_block, chunks[2]);
         })?;
+        app.areas = areas;
 
-        // Handle input for dialog fields
+        // Handle user input
         if let Ok(true) = event::poll(std::time::Duration::from_millis(100)) {
-            if let Ok(event::Event::Key(KeyEvent {
-                code, modifiers: _, ..
-            })) = event::read()
-            {
-                match code {
-                    KeyCode::Enter => {
-                        // Create a new task and add it to the database
-                        let new_task = db::Task {
-                            id: 0, // This will be auto-incremented
-                            title: title.clone(),
-                            description: description.clone(),
-                            category: selected_category.clone(),
-                            parent_task_id, // Set parent task ID if selected
-                            due_date: Some(due_date.clone()),
-                            time: Some(time.clone()),
-                            priority: selected_priority.clone(),
-                            status: selected_status.clone(),
-                        };
-                        db.add_task(&new_task)?;
-                        break; // Exit the dialog
+            let event = event::read();
+            if let Ok(event::Event::Mouse(mouse)) = &event {
+                if app.handle_mouse(mouse) {
+                    if let Some(task) = app.selected_task() {
+                        show_task_dialog(&db, Some(&task), &theme)?;
+                        terminal.clear()?;
+                        app.reload(&db)?;
+                        if !app.workflow.is_done(&task.status) {
+                            app.offer_to_complete_parent(&[task.id]);
+                        }
                     }
-                    KeyCode::Esc => break, // Exit the dialog
-                    KeyCode::Tab => {
-                        // Switch focus to the next field
-                        focused_field = match focused_field {
-                            FocusedField::Title => FocusedField::Description,
-                            FocusedField::Description => FocusedField::Category,
-                            FocusedField::Category => FocusedField::ParentTask,
-                            FocusedField::ParentTask => FocusedField::DueDate,
-                            FocusedField::DueDate => FocusedField::Time,
-                            FocusedField::Time => FocusedField::Priority,
-                            FocusedField::Priority => FocusedField::Status,
-                            FocusedField::Status => FocusedField::Title,
-                        };
+                }
+                continue;
+            }
+            if let Ok(event::Event::Key(key)) = event {
+                if let Some(Prompt {
+                    kind: PromptKind::CompleteParent(_),
+                    ..
+                }) = &app.prompt
+                {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Enter => {
+                            if let Some(prompt) = app.prompt.take() {
+                                app.submit_prompt(&db, prompt)?;
+                            }
+                        }
+                        KeyCode::Char('n') | KeyCode::Esc => app.prompt = None,
+                        _ => {}
                     }
-                    KeyCode::BackTab => {
-                        // Switch focus to the previous field
-                        focused_field = match focused_field {
-                            FocusedField::Title => FocusedField::Status,
-                            FocusedField::Description => FocusedField::Title,
-                            FocusedField::Category => FocusedField::Description,
-                            FocusedField::ParentTask => FocusedField::Category,
-                            FocusedField::DueDate => FocusedField::ParentTask,
-                            FocusedField::Time => FocusedField::DueDate,
-                            FocusedField::Priority => FocusedField::Time,
-                            FocusedField::Status => FocusedField::Priority,
-                        };
+                } else if let Some(prompt) = &mut app.prompt {
+                    match key.code {
+                        KeyCode::Esc => {
+                            app.prompt = None;
+                            app.message = None;
+                        }
+                        KeyCode::Enter => {
+                            if let Some(prompt) = app.prompt.take() {
+                                app.message = None;
+                                app.submit_prompt(&db, prompt)?;
+                            }
+                        }
+                        KeyCode::Backspace => {
+                            prompt.input.pop();
+                        }
+                        KeyCode::Char(c) => prompt.input.push(c),
+                        _ => {}
                     }
-                    KeyCode::Char(c) => {
-                        // Update the input fields based on the current context
-                        match focused_field {
-                            FocusedField::Title => title.push(c),
-                            FocusedField::Description => description.push(c),
-                            FocusedField::DueDate => due_date.push(c),
-                            FocusedField::Time => time.push(c),
-                            _ => {}
+                    continue;
+                }
+                if app.searching {
+                    // The search bar swallows every key until it is closed
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Enter => app.searching = false,
+                        KeyCode::Backspace => {
+                            app.search_query.pop();
                         }
+                        KeyCode::Char(c) => app.search_query.push(c),
+                        _ => {}
                     }
-                    KeyCode::Backspace => {
-                        // Handle backspace for the active input field
-                        match focused_field {
-                            FocusedField::Title => {
-                                title.pop();
+                    app.apply_search(&db)?;
+                    continue;
+                }
+
+                let action = keymap.action(&key);
+                // The task lists are covered while planning
+                if app.planning.is_some()
+                    && !matches!(
+                        action,
+                        Some(
+                            Action::Planning
+                                | Action::Quit
+                                | Action::SwitchTheme
+                                | Action::SwitchProfile
+                        )
+                    )
+                {
+                    continue;
+                }
+                match action {
+                    Some(Action::Add) => {
+                        show_task_dialog(&db, None, &theme)?;
+                        terminal.clear()?;
+                        app.reload(&db)?;
+                    }
+                    Some(Action::Edit) => {
+                        if let Some(task) = app.selected_task() {
+                            show_task_dialog(&db, Some(&task), &theme)?;
+                            terminal.clear()?;
+                            app.reload(&db)?;
+                            if !app.workflow.is_done(&task.status) {
+                                app.offer_to_complete_parent(&[task.id]);
+                            }
+                        }
+                    }
+                    Some(Action::Complete) => {
+                        let ids = app.target_ids();
+                        app.bulk(&db, "completed", false, |db, task| {
+                            Ok(db.set_status(task.id, db.workflow().completed())?)
+                        })?;
+                        app.offer_to_complete_parent(&ids);
+                    }
+                    // In the Attachments pane, Delete removes the selected attachment
+                    Some(Action::Delete) if app.pane == Pane::Attachments => {
+                        match app.selected_link() {
+                            Some(attachments::Link {
+                                attachment_id: Some(id),
+                                ..
+                            }) => {
+                                db.delete_attachment(id)?;
+                                app.reload(&db)?;
                             }
-                            FocusedField::Description => {
-                                description.pop();
+                            Some(_) => {
+                                app.message =
+                                    Some("edit the description to remove this link".to_string())
                             }
-                            FocusedField::DueDate => {
-                                due_date.pop();
+                            None => {}
+                        }
+                    }
+                    // Trashed tasks are deleted for good
+                    Some(Action::Delete) => app.bulk(&db, "deleted", true, |db, task| {
+                        if task.trashed_at.is_some() {
+                            Ok(db.delete_task(task.id)?)
+                        } else {
+                            Ok(db.trash_task(task.id)?)
+                        }
+                    })?,
+                    Some(Action::Archive) => app.bulk(&db, "archived", true, |db, task| {
+                        Ok(db.archive_task(task.id)?)
+                    })?,
+                    Some(Action::Restore) => app.bulk(&db, "restored", true, |db, task| {
+                        Ok(db.restore_task(task.id)?)
+                    })?,
+                    Some(Action::Search) => app.searching = true,
+                    Some(Action::SwitchView) => app.switch_pane(),
+                    Some(Action::NextSavedView) => app.switch_view(&db, 1)?,
+                    Some(Action::PrevSavedView) => app.switch_view(&db, -1)?,
+                    Some(Action::SaveView) => app.open_prompt(PromptKind::SaveView),
+                    Some(Action::DeleteView) => {
+                        if let Some(view) = app.active_view() {
+                            db.delete_view(view.id)?;
+                            app.active_view = 0;
+                            app.reload(&db)?;
+                        }
+                    }
+                    Some(Action::CycleSort) => app.cycle_sort(),
+                    Some(Action::Planning) => {
+                        app.planning = match app.planning {
+                            None => Some(planning::Period::Day),
+                            Some(planning::Period::Day) => Some(planning::Period::Week),
+                            Some(planning::Period::Week) => None,
+                        }
+                    }
+                    Some(Action::ToggleSelect) => app.toggle_mark(),
+                    Some(Action::SelectRange) => app.toggle_range(),
+                    Some(Action::SelectAll) => app.mark_all(),
+                    Some(Action::BulkEdit) => {
+                        if !app.target_ids().is_empty() {
+                            app.open_prompt(PromptKind::BulkEdit);
+                        }
+                    }
+                    Some(Action::QuickAdd) => app.open_prompt(PromptKind::QuickAdd),
+                    Some(Action::FromTemplate) => {
+                        if app.templates.is_empty() {
+                            app.message = Some(format!(
+                                "no templates; add TOML files to {}",
+                                templates_dir
+                                    .as_deref()
+                                    .map_or("templates".into(), |dir| dir.display().to_string())
+                            ));
+                        } else {
+                            app.open_prompt(PromptKind::Template);
+                        }
+                    }
+                    Some(Action::Attach) => {
+                        if let Some(task) = app.detail_task() {
+                            let id = task.id;
+                            app.open_prompt(PromptKind::Attach(id));
+                        }
+                    }
+                    Some(Action::OpenAttachment) => {
+                        if let Some(link) = app.selected_link() {
+                            app.message = attachments::open(&link.target).err();
+                        }
+                    }
+                    Some(Action::Undo) => app.undo(&db)?,
+                    Some(Action::SwitchTheme) => {
+                        state.theme = Some(themes.next().name.clone());
+                        state.save()?;
+                    }
+                    Some(Action::SwitchProfile) => {
+                        let current = profiles.iter().position(|p| *p == profile).unwrap_or(0);
+                        let next = &profiles[(current + 1) % profiles.len()];
+                        let opened =
+                            open_database(next, &config.backup, &workflow).and_then(|opened| {
+                                if config.backup.on_startup {
+                                    backup::take(&opened, &config.backup, "startup")?;
+                                }
+                                Ok(opened)
+                            });
+                        // A cancelled passphrase or unreadable file keeps the current profile
+                        match opened {
+                            Ok(opened) => {
+                                db = opened;
+                                profile = next.clone();
+                                app.message = auto_archive(&db, &config)?;
+                                app.active_view = 0;
+                                app.marked.clear();
+                                app.range_anchor = None;
+                                app.undo_stack.clear();
+                                app.reload(&db)?;
                             }
-                            FocusedField::Time => {
-                                time.pop();
+                            Err(e) => {
+                                app.message =
+                                    Some(format!("can't open profile '{}': {}", next.name, e))
                             }
-                            _ => {}
                         }
                     }
-                    _ => {}
+                    // The guard restores the terminal
+                    Some(Action::Quit) => return Ok(()),
+                    None => match key.code {
+                        KeyCode::Up => app.move_selection(-1),
+                        KeyCode::Down => app.move_selection(1),
+                        _ => {}
+                    },
+                }
+            }
+        }
+    }
+}
+
+fn show_task_dialog(
+    db: &db::Database,
+    existing: Option<&db::Task>,
+    theme: &Theme,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let mut form = TaskForm::new(existing, &db.get_all_tasks()?, db.workflow());
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+    loop {
+        let today = Local::now().date_naive();
+        terminal.draw(|f| form.render(f, theme, today))?;
+
+        if let Ok(true) = event::poll(std::time::Duration::from_millis(100)) {
+            let outcome = match event::read() {
+                Ok(event::Event::Key(key)) => form.handle_key(&key, today),
+                Ok(event::Event::Mouse(mouse)) => form.handle_mouse(&mouse, today),
+                Ok(event::Event::Paste(text)) => {
+                    form.paste(&text);
+                    continue;
+                }
+                _ => continue,
+            };
+            match outcome {
+                Outcome::Continue => {}
+                Outcome::Cancel => break,
+                Outcome::Save(task) => {
+                    if existing.is_some() {
+                        db.update_task(&task)?;
+                    } else {
+                        db.add_task(&task)?;
+                    }
+                    break;
+                }
+                Outcome::OpenEditor => {
+                    // The editor gets the terminal as a plain one, without
+                    // mouse reports or paste markers in its input
+                    execute!(
+                        io::stdout(),
+                        event::DisableMouseCapture,
+                        event::DisableBracketedPaste
+                    )?;
+                    terminal::disable_raw_mode()?;
+                    let edited = textarea::edit_externally(form.description());
+                    terminal::enable_raw_mode()?;
+                    execute!(
+                        io::stdout(),
+                        event::EnableBracketedPaste,
+                        event::EnableMouseCapture
+                    )?;
+                    terminal.clear()?;
+                    form.finish_editing(edited);
                 }
             }
         }
@@ -300,3 +2003,30 @@ fn show_task_dialog(db: &db::Database) -> std::result::Result<(), Box<dyn std::e
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_profiles_fail_without_changes() {
+        let path =
+            std::env::temp_dir().join(format!("task_manager_profile_{}.db", std::process::id()));
+        let contents = "not a task database, just some notes that are long enough";
+        std::fs::write(&path, contents).unwrap();
+        let profile = config::Profile {
+            name: "notes".to_string(),
+            path: path.clone(),
+        };
+        let opened = open_database(
+            &profile,
+            &config::BackupConfig::default(),
+            &workflow::Workflow::default(),
+        );
+        let after = std::fs::read_to_string(&path);
+        let _ = std::fs::remove_file(&path);
+        // The profile switch shows the error and keeps the open database
+        assert!(opened.is_err());
+        assert_eq!(after.unwrap(), contents);
+    }
+}
@@ -0,0 +1,538 @@
+use crate::db::{self, Database, Scope, SyncRecord, TRACKED_FIELDS};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// A field changed on both sides since the last merge (or at the same instant)
+struct Conflict {
+    uuid: String,
+    title: String,
+    field: &'static str,
+    local: (Option<String>, String),
+    other: (Option<String>, String),
+}
+
+/// Field value with the parent expressed as a uuid, so both sides compare
+fn value(record: &SyncRecord, field: &str) -> Option<String> {
+    match field {
+        "parent" => record.parent_uuid.clone(),
+        _ => record.task.field(field),
+    }
+}
+
+fn set_value(record: &mut SyncRecord, field: &str, value: Option<String>, changed_at: &str) {
+    match field {
+        "parent" => record.parent_uuid = value,
+        _ => record.task.set_field(field, value),
+    }
+    record
+        .field_times
+        .insert(field.to_string(), changed_at.to_string());
+    if record.task.updated_at.as_str() < changed_at {
+        record.task.updated_at = changed_at.to_string();
+    }
+}
+
+fn changed_at<'a>(record: &'a SyncRecord, field: &str) -> &'a str {
+    record
+        .field_times
+        .get(field)
+        .unwrap_or(&record.task.updated_at)
+}
+
+fn show(value: &Option<String>) -> String {
+    match value {
+        Some(value) => format!("{:?}", value),
+        None => "(empty)".to_string(),
+    }
+}
+
+/// Whether following parents up from `uuid` leads back to it
+fn in_cycle(parents: &HashMap<String, Option<String>>, uuid: &str) -> bool {
+    let mut seen = HashSet::new();
+    let mut current = parents.get(uuid).cloned().flatten();
+    while let Some(parent) = current {
+        if parent == uuid {
+            return true;
+        }
+        if !seen.insert(parent.clone()) {
+            return false;
+        }
+        current = parents.get(&parent).cloned().flatten();
+    }
+    false
+}
+
+/// Undo parent changes that would nest a task under itself, e.g. when each
+/// side moved one of two tasks under the other. The local parent is kept, or
+/// none when that still forms a cycle.
+fn break_cycles(
+    local: &HashMap<String, SyncRecord>,
+    changes: &mut HashMap<String, SyncRecord>,
+    deleted: &HashSet<&String>,
+) {
+    let mut parents: HashMap<String, Option<String>> = local
+        .iter()
+        .chain(changes.iter())
+        .filter(|(uuid, _)| !deleted.contains(uuid))
+        .map(|(uuid, record)| (uuid.clone(), record.parent_uuid.clone()))
+        .collect();
+    let mut uuids: Vec<String> = changes.keys().cloned().collect();
+    uuids.sort();
+    for uuid in uuids {
+        if !in_cycle(&parents, &uuid) {
+            continue;
+        }
+        let record = changes.get_mut(&uuid).unwrap();
+        let local_parent = local.get(&uuid).and_then(|mine| mine.parent_uuid.clone());
+        record.parent_uuid = local_parent.clone();
+        parents.insert(uuid.clone(), local_parent);
+        if in_cycle(&parents, &uuid) {
+            record.parent_uuid = None;
+            parents.insert(uuid.clone(), None);
+        }
+        println!(
+            "Keeping \"{}\" where it was: the merged parent would nest it under itself",
+            record.task.title
+        );
+    }
+}
+
+/// Attachments, dependencies and commit links of `other` that `db` lacks.
+/// They have no change history, so merge can't tell additions from removals
+/// and leaves them out.
+fn unmerged(db: &Database, other: &Database) -> rusqlite::Result<Vec<String>> {
+    let uuids = |db: &Database| -> rusqlite::Result<HashMap<i32, String>> {
+        Ok(db
+            .get_tasks(Scope::All)?
+            .into_iter()
+            .map(|task| (task.id, task.uuid))
+            .collect())
+    };
+    let (local_uuids, other_uuids) = (uuids(db)?, uuids(other)?);
+    let attachments = |db: &Database, uuids: &HashMap<i32, String>| {
+        db.attachments().map(|attachments| {
+            attachments
+                .into_values()
+                .flatten()
+                .map(|a| (uuids.get(&a.task_id).cloned(), a.target))
+                .collect::<HashSet<_>>()
+        })
+    };
+    let dependencies = |db: &Database, uuids: &HashMap<i32, String>| {
+        db.dependencies().map(|dependencies| {
+            dependencies
+                .into_iter()
+                .flat_map(|(id, on)| on.into_iter().map(move |on| (id, on)))
+                .map(|(id, on)| (uuids.get(&id).cloned(), uuids.get(&on).cloned()))
+                .collect::<HashSet<_>>()
+        })
+    };
+    let commits = |db: &Database, uuids: &HashMap<i32, String>| {
+        db.commit_links().map(|links| {
+            links
+                .into_values()
+                .flatten()
+                .map(|link| (uuids.get(&link.task_id).cloned(), link.hash))
+                .collect::<HashSet<_>>()
+        })
+    };
+    let counts = [
+        (
+            "attachment(s)",
+            attachments(other, &other_uuids)?
+                .difference(&attachments(db, &local_uuids)?)
+                .count(),
+        ),
+        (
+            "dependency(ies)",
+            dependencies(other, &other_uuids)?
+                .difference(&dependencies(db, &local_uuids)?)
+                .count(),
+        ),
+        (
+            "commit link(s)",
+            commits(other, &other_uuids)?
+                .difference(&commits(db, &local_uuids)?)
+                .count(),
+        ),
+    ];
+    Ok(counts
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(name, count)| format!("{} {}", count, name))
+        .collect())
+}
+
+/// Ask which side of a conflict to keep; an empty answer or EOF keeps the newer
+fn resolve(conflict: &Conflict, input: &mut impl BufRead) -> io::Result<bool> {
+    let other_is_newer = conflict.other.1 > conflict.local.1;
+    println!("Conflict in \"{}\" ({}):", conflict.title, conflict.field);
+    println!(
+        "  [l] local: {}  (changed {})",
+        show(&conflict.local.0),
+        conflict.local.1
+    );
+    println!(
+        "  [o] other: {}  (changed {})",
+        show(&conflict.other.0),
+        conflict.other.1
+    );
+    loop {
+        print!(
+            "Keep which? [l/o] (default {}): ",
+            if other_is_newer { "o" } else { "l" }
+        );
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(other_is_newer);
+        }
+        match answer.trim() {
+            "" => return Ok(other_is_newer),
+            "l" | "local" => return Ok(false),
+            "o" | "other" => return Ok(true),
+            _ => println!("Please answer l or o."),
+        }
+    }
+}
+
+/// Merge the tasks in `other_path` into `db`. Tasks are matched by uuid and
+/// each field takes the most recently changed value; fields changed on both
+/// sides since the previous merge are resolved interactively. Deletions carry
+/// over through tombstones. Only `db` is written; the other database is
+/// opened read-only and must have the current schema.
+pub fn merge(db: &Database, other_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    merge_with(db, other_path, &mut io::stdin().lock())
+}
+
+/// `merge`, reading the answers to conflict questions from `input`
+fn merge_with(
+    db: &Database,
+    other_path: &Path,
+    input: &mut impl BufRead,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !other_path.is_file() {
+        return Err(format!("{} does not exist", other_path.display()).into());
+    }
+    let other = crate::encryption::open_read_only(other_path, true)?;
+    if !other.is_current()? {
+        return Err(format!(
+            "{} has an older schema; upgrade it by opening it once with `task_manager --db {}`",
+            other_path.display(),
+            other_path.display()
+        )
+        .into());
+    }
+    let base_key = format!("merged:{}", other_path.canonicalize()?.display());
+    let base = db.meta(&base_key)?.unwrap_or_default();
+
+    let local: HashMap<String, SyncRecord> = db
+        .sync_records()?
+        .into_iter()
+        .map(|record| (record.task.uuid.clone(), record))
+        .collect();
+    let local_tombstones = db.tombstones()?;
+    let other_tombstones = other.tombstones()?;
+
+    let mut changes: HashMap<String, SyncRecord> = HashMap::new();
+    let mut conflicts = Vec::new();
+    let (mut added, mut deleted) = (0, 0);
+
+    for theirs in other.sync_records()? {
+        let uuid = theirs.task.uuid.clone();
+        match local.get(&uuid) {
+            Some(mine) => {
+                let mut merged = mine.clone();
+                let mut changed = false;
+                for field in TRACKED_FIELDS {
+                    let (mine_value, their_value) = (value(mine, field), value(&theirs, field));
+                    if mine_value == their_value {
+                        continue;
+                    }
+                    let (mine_at, their_at) = (changed_at(mine, field), changed_at(&theirs, field));
+                    let both_changed =
+                        !base.is_empty() && mine_at > base.as_str() && their_at > base.as_str();
+                    if both_changed || mine_at == their_at {
+                        conflicts.push(Conflict {
+                            uuid: uuid.clone(),
+                            title: mine.task.title.clone(),
+                            field,
+                            local: (mine_value, mine_at.to_string()),
+                            other: (their_value, their_at.to_string()),
+                        });
+                    } else if their_at > mine_at {
+                        set_value(&mut merged, field, their_value, their_at);
+                        changed = true;
+                    }
+                }
+                if changed {
+                    changes.insert(uuid, merged);
+                }
+            }
+            None => match local_tombstones.get(&uuid) {
+                Some(deleted_at) if theirs.task.updated_at <= *deleted_at => {}
+                Some(_) => {
+                    println!(
+                        "Restoring \"{}\": it was edited in {} after being deleted here",
+                        theirs.task.title,
+                        other_path.display()
+                    );
+                    added += 1;
+                    changes.insert(uuid, theirs);
+                }
+                None => {
+                    added += 1;
+                    changes.insert(uuid, theirs);
+                }
+            },
+        }
+    }
+
+    let mut deletions = Vec::new();
+    for (uuid, deleted_at) in &other_tombstones {
+        if local_tombstones.contains_key(uuid) {
+            continue;
+        }
+        match local.get(uuid) {
+            Some(mine) if mine.task.updated_at > *deleted_at => println!(
+                "Keeping \"{}\": it was edited here after being deleted in {}",
+                mine.task.title,
+                other_path.display()
+            ),
+            Some(_) => {
+                deleted += 1;
+                deletions.push((uuid, deleted_at));
+            }
+            None => deletions.push((uuid, deleted_at)),
+        }
+    }
+
+    // Ask about conflicts before touching the database
+    let resolved_at = db::now();
+    for conflict in &conflicts {
+        let keep_other = resolve(conflict, input)?;
+        let record = changes
+            .entry(conflict.uuid.clone())
+            .or_insert_with(|| local[&conflict.uuid].clone());
+        let chosen = if keep_other {
+            &conflict.other.0
+        } else {
+            &conflict.local.0
+        };
+        // Stamp the choice with the current time so it wins on the next merge
+        set_value(record, conflict.field, chosen.clone(), &resolved_at);
+    }
+
+    let deleted_uuids: HashSet<&String> = deletions.iter().map(|(uuid, _)| *uuid).collect();
+    break_cycles(&local, &mut changes, &deleted_uuids);
+
+    let updated = changes.len() - added;
+    db.transaction(|db| -> rusqlite::Result<()> {
+        for record in changes.values() {
+            db.upsert_sync_record(record)?;
+        }
+        for record in changes.values() {
+            db.set_parent_uuid(&record.task.uuid, record.parent_uuid.as_deref())?;
+        }
+        for (uuid, deleted_at) in &deletions {
+            db.delete_by_uuid(uuid, deleted_at)?;
+        }
+        db.set_meta(&base_key, &db::now())
+    })?;
+
+    println!(
+        "Merged {}: {} added, {} updated, {} deleted, {} conflict(s) resolved",
+        other_path.display(),
+        added,
+        updated,
+        deleted,
+        conflicts.len()
+    );
+    let left_out = unmerged(db, &other)?;
+    if !left_out.is_empty() {
+        println!(
+            "Not merged, as their removals aren't tracked: {} only in {}",
+            left_out.join(", "),
+            other_path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Task;
+    use std::path::PathBuf;
+
+    const BEFORE: &str = "2026-10-01T00:00:00.000Z";
+    const LAST_MERGE: &str = "2026-10-02T00:00:00.000Z";
+    const EARLIER: &str = "2026-10-03T00:00:00.000Z";
+    const LATER: &str = "2026-10-04T00:00:00.000Z";
+
+    /// A database file in the temp directory, removed when dropped
+    struct TempDatabase {
+        path: PathBuf,
+        db: Database,
+    }
+
+    impl TempDatabase {
+        fn new(test: &str, side: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "task_manager_merge_{}_{}_{}.db",
+                std::process::id(),
+                test,
+                side
+            ));
+            remove(&path);
+            let db = Database::new(&path).unwrap();
+            db.migrate().unwrap();
+            TempDatabase { path, db }
+        }
+
+        fn save(&self, records: &[SyncRecord]) {
+            for record in records {
+                self.db.upsert_sync_record(record).unwrap();
+            }
+            for record in records {
+                self.db
+                    .set_parent_uuid(&record.task.uuid, record.parent_uuid.as_deref())
+                    .unwrap();
+            }
+        }
+
+        fn get(&self, uuid: &str) -> Option<SyncRecord> {
+            self.db
+                .sync_records()
+                .unwrap()
+                .into_iter()
+                .find(|record| record.task.uuid == uuid)
+        }
+
+        /// Merge `other` in, answering conflict questions with `answers`
+        fn merge(&self, other: &TempDatabase, answers: &str) {
+            merge_with(&self.db, &other.path, &mut answers.as_bytes()).unwrap();
+        }
+
+        /// Record a merge with `other` at `LAST_MERGE`
+        fn merged_before(&self, other: &TempDatabase) {
+            let key = format!("merged:{}", other.path.canonicalize().unwrap().display());
+            self.db.set_meta(&key, LAST_MERGE).unwrap();
+        }
+    }
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            remove(&self.path);
+        }
+    }
+
+    fn remove(path: &Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    /// A task whose fields all last changed at `at`
+    fn record(uuid: &str, title: &str, at: &str) -> SyncRecord {
+        SyncRecord {
+            task: Task {
+                title: title.to_string(),
+                category: "Work".to_string(),
+                priority: "Medium".to_string(),
+                status: "To Do".to_string(),
+                uuid: uuid.to_string(),
+                updated_at: at.to_string(),
+                created_at: at.to_string(),
+                ..Default::default()
+            },
+            parent_uuid: None,
+            field_times: TRACKED_FIELDS
+                .iter()
+                .map(|field| (field.to_string(), at.to_string()))
+                .collect(),
+        }
+    }
+
+    fn changed(record: &SyncRecord, field: &str, value: &str, at: &str) -> SyncRecord {
+        let mut record = record.clone();
+        set_value(&mut record, field, Some(value.to_string()), at);
+        record
+    }
+
+    #[test]
+    fn one_sided_changes_take_the_newer_value() {
+        let (local, other) = (
+            TempDatabase::new("one_sided", "local"),
+            TempDatabase::new("one_sided", "other"),
+        );
+        let task = record("a", "Write report", BEFORE);
+        local.save(&[changed(&task, "description", "Two pages", EARLIER)]);
+        other.save(&[changed(&task, "priority", "High", LATER)]);
+        local.merged_before(&other);
+
+        // Were either change a conflict, answering "l" would keep the local value
+        local.merge(&other, "l\nl\n");
+        let merged = local.get("a").unwrap().task;
+        assert_eq!(merged.description, "Two pages");
+        assert_eq!(merged.priority, "High");
+    }
+
+    #[test]
+    fn changes_on_both_sides_conflict() {
+        let (local, other) = (
+            TempDatabase::new("both_sides", "local"),
+            TempDatabase::new("both_sides", "other"),
+        );
+        let task = record("a", "Write report", BEFORE);
+        local.save(&[changed(&task, "title", "Write the report", EARLIER)]);
+        other.save(&[changed(&task, "title", "Write a report", LATER)]);
+        local.merged_before(&other);
+
+        // The other side is newer, so only a conflict lets "l" keep the local title
+        local.merge(&other, "l\n");
+        assert_eq!(local.get("a").unwrap().task.title, "Write the report");
+    }
+
+    #[test]
+    fn local_edits_outlive_older_deletions() {
+        let (local, other) = (
+            TempDatabase::new("tombstones", "local"),
+            TempDatabase::new("tombstones", "other"),
+        );
+        let edited = record("a", "Write report", BEFORE);
+        let untouched = record("b", "Book flights", BEFORE);
+        local.save(&[
+            changed(&edited, "title", "Write the report", LATER),
+            untouched.clone(),
+        ]);
+        other.save(&[edited, untouched]);
+        other.db.delete_by_uuid("a", EARLIER).unwrap();
+        other.db.delete_by_uuid("b", EARLIER).unwrap();
+
+        local.merge(&other, "");
+        assert_eq!(local.get("a").unwrap().task.title, "Write the report");
+        assert!(local.get("b").is_none());
+    }
+
+    #[test]
+    fn mutual_re_parenting_keeps_the_tree() {
+        let (local, other) = (
+            TempDatabase::new("re_parenting", "local"),
+            TempDatabase::new("re_parenting", "other"),
+        );
+        let (a, b) = (
+            record("a", "Plan trip", BEFORE),
+            record("b", "Book flights", BEFORE),
+        );
+        // Each side nests one task under the other
+        local.save(&[changed(&a, "parent", "b", EARLIER), b.clone()]);
+        other.save(&[a, changed(&b, "parent", "a", LATER)]);
+        local.merged_before(&other);
+
+        local.merge(&other, "");
+        assert_eq!(local.get("a").unwrap().parent_uuid.as_deref(), Some("b"));
+        assert_eq!(local.get("b").unwrap().parent_uuid, None);
+    }
+}
@@ -0,0 +1,280 @@
+//! Effort estimates and progress rolled up from sub-tasks, and the planning
+//! view's sums of the estimates due per day or week against the `[planning]`
+//! capacity.
+
+use crate::config::PlanningConfig;
+use crate::db::Task;
+use crate::workflow::Workflow;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use std::collections::{HashMap, HashSet};
+
+/// A task's estimate plus those of all its sub-tasks, and how many of the
+/// sub-tasks are done
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rollup {
+    pub total: f64,
+    /// Only unfinished tasks
+    pub remaining: f64,
+    /// Sub-tasks at any depth, leaving out archived and trashed ones
+    pub sub_tasks: usize,
+    pub sub_tasks_done: usize,
+    /// Estimates of those sub-tasks, and of the completed ones
+    pub sub_estimate: f64,
+    pub sub_estimate_done: f64,
+}
+
+impl Rollup {
+    /// Share of the sub-tasks that are done, weighted by estimate if asked
+    /// and any has one. None without sub-tasks.
+    pub fn progress(&self, by_estimate: bool) -> Option<f64> {
+        if self.sub_tasks == 0 {
+            None
+        } else if by_estimate && self.sub_estimate > 0.0 {
+            Some(self.sub_estimate_done / self.sub_estimate)
+        } else {
+            Some(self.sub_tasks_done as f64 / self.sub_tasks as f64)
+        }
+    }
+
+    pub fn all_done(&self) -> bool {
+        self.sub_tasks > 0 && self.sub_tasks_done == self.sub_tasks
+    }
+}
+
+/// Rolled-up estimates and progress by task id
+pub fn rollups(tasks: &[Task], workflow: &Workflow) -> HashMap<i32, Rollup> {
+    let parents: HashMap<i32, Option<i32>> = tasks
+        .iter()
+        .map(|task| (task.id, task.parent_task_id))
+        .collect();
+    let mut rollups: HashMap<i32, Rollup> = HashMap::new();
+    for task in tasks {
+        let estimate = task.estimate.unwrap_or(0.0);
+        let done = workflow.is_done(&task.status);
+        let remaining = if done { 0.0 } else { estimate };
+        // Add to the task and each ancestor, stopping at a cycle
+        let mut seen = HashSet::new();
+        let mut id = Some(task.id);
+        while let Some(current) = id.filter(|id| seen.insert(*id)) {
+            let rollup = rollups.entry(current).or_default();
+            rollup.total += estimate;
+            rollup.remaining += remaining;
+            if current != task.id && !task.is_hidden() {
+                rollup.sub_tasks += 1;
+                rollup.sub_estimate += estimate;
+                if done {
+                    rollup.sub_tasks_done += 1;
+                    rollup.sub_estimate_done += estimate;
+                }
+            }
+            id = parents.get(&current).copied().flatten();
+        }
+    }
+    rollups
+}
+
+/// "2.5 h", dropping a trailing ".0"
+pub fn format_estimate(estimate: f64, unit: &str) -> String {
+    let number = format!("{:.1}", estimate);
+    let number = number.strip_suffix(".0").unwrap_or(&number);
+    if unit.is_empty() {
+        number.to_string()
+    } else {
+        format!("{} {}", number, unit)
+    }
+}
+
+pub fn work_days(config: &PlanningConfig) -> Result<Vec<Weekday>, String> {
+    config
+        .work_days
+        .iter()
+        .map(|day| {
+            day.parse::<Weekday>()
+                .map_err(|_| format!("planning.work_days: '{}' is not a weekday", day))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+}
+
+/// Estimates due in one day or week
+pub struct Bucket {
+    pub label: String,
+    pub tasks: usize,
+    /// Tasks counted without an estimate
+    pub unestimated: usize,
+    pub load: f64,
+    /// None for the overdue row, which has no capacity of its own
+    pub capacity: Option<f64>,
+}
+
+impl Bucket {
+    pub fn is_over(&self) -> bool {
+        self.capacity.is_some_and(|capacity| self.load > capacity)
+    }
+}
+
+pub struct Plan {
+    pub buckets: Vec<Bucket>,
+    /// Unfinished tasks with neither a due date nor a parent that has one
+    pub unscheduled: usize,
+}
+
+/// Due date of the task, or of its nearest ancestor that has one
+fn effective_due(task: &Task, by_id: &HashMap<i32, &Task>) -> Option<NaiveDate> {
+    let mut seen = HashSet::new();
+    let mut current = Some(task);
+    while let Some(task) = current.filter(|task| seen.insert(task.id)) {
+        if let Some(due) = task
+            .due_date
+            .as_deref()
+            .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok())
+        {
+            return Some(due);
+        }
+        current = task.parent_task_id.and_then(|id| by_id.get(&id).copied());
+    }
+    None
+}
+
+/// Unfinished work due in each of the next `count` days or weeks, after a
+/// row for anything overdue. Each task counts its own estimate, so sub-tasks
+/// and their parents aren't counted twice.
+pub fn plan(
+    tasks: &[Task],
+    period: Period,
+    count: usize,
+    config: &PlanningConfig,
+    work_days: &[Weekday],
+    workflow: &Workflow,
+    today: NaiveDate,
+) -> Plan {
+    let start = match period {
+        Period::Day => today,
+        Period::Week => today - Duration::days(today.weekday().num_days_from_monday() as i64),
+    };
+    let length = match period {
+        Period::Day => 1,
+        Period::Week => 7,
+    };
+    let capacity = |first: NaiveDate| {
+        (0..length)
+            .map(|i| first + Duration::days(i))
+            .filter(|day| work_days.contains(&day.weekday()))
+            .count() as f64
+            * config.daily_capacity
+    };
+
+    let mut buckets = vec![Bucket {
+        label: "Overdue".to_string(),
+        tasks: 0,
+        unestimated: 0,
+        load: 0.0,
+        capacity: None,
+    }];
+    for i in 0..count as i64 {
+        let first = start + Duration::days(i * length);
+        buckets.push(Bucket {
+            label: match period {
+                Period::Day if first == today => format!("Today {}", first.format("%a %d %b")),
+                Period::Day => first.format("%a %d %b").to_string(),
+                Period::Week => format!("Week of {}", first.format("%d %b")),
+            },
+            tasks: 0,
+            unestimated: 0,
+            load: 0.0,
+            capacity: Some(capacity(first)),
+        });
+    }
+
+    let by_id: HashMap<i32, &Task> = tasks.iter().map(|task| (task.id, task)).collect();
+    let mut unscheduled = 0;
+    for task in tasks
+        .iter()
+        .filter(|task| !workflow.is_done(&task.status) && !task.is_hidden())
+    {
+        let index = match effective_due(task, &by_id) {
+            None => {
+                unscheduled += 1;
+                continue;
+            }
+            Some(due) if due < today => 0,
+            Some(due) => {
+                let offset = (due - start).num_days() / length;
+                match usize::try_from(offset + 1) {
+                    Ok(index) if index < buckets.len() => index,
+                    _ => continue,
+                }
+            }
+        };
+        let bucket = &mut buckets[index];
+        bucket.tasks += 1;
+        match task.estimate {
+            Some(estimate) => bucket.load += estimate,
+            None => bucket.unestimated += 1,
+        }
+    }
+    Plan {
+        buckets,
+        unscheduled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i32, parent: Option<i32>, status: &str, estimate: Option<f64>) -> Task {
+        Task {
+            id,
+            parent_task_id: parent,
+            status: status.to_string(),
+            estimate,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rolls_up_sub_tasks() {
+        let tasks = [
+            task(1, None, "To Do", Some(1.0)),
+            task(2, Some(1), "Completed", Some(2.0)),
+            task(3, Some(1), "To Do", Some(3.0)),
+            task(4, Some(3), "Completed", None),
+        ];
+        let rollups = rollups(&tasks, &Workflow::default());
+        let parent = rollups[&1];
+        assert_eq!((parent.total, parent.remaining), (6.0, 4.0));
+        assert_eq!((parent.sub_tasks_done, parent.sub_tasks), (2, 3));
+        assert_eq!(parent.progress(false), Some(2.0 / 3.0));
+        assert_eq!(parent.progress(true), Some(2.0 / 5.0));
+        assert!(!parent.all_done());
+        assert!(rollups[&3].all_done());
+        assert_eq!(rollups[&4].progress(false), None);
+    }
+
+    #[test]
+    fn leaves_out_hidden_sub_tasks() {
+        let archived = Task {
+            archived_at: Some("2026-10-01T00:00:00.000Z".to_string()),
+            ..task(3, Some(1), "To Do", None)
+        };
+        let trashed = Task {
+            trashed_at: Some("2026-10-01T00:00:00.000Z".to_string()),
+            ..task(4, Some(1), "In Progress", None)
+        };
+        let tasks = [
+            task(1, None, "To Do", None),
+            task(2, Some(1), "Completed", None),
+            archived,
+            trashed,
+        ];
+        let parent = rollups(&tasks, &Workflow::default())[&1];
+        assert_eq!((parent.sub_tasks_done, parent.sub_tasks), (1, 1));
+        assert!(parent.all_done());
+    }
+}
@@ -0,0 +1,81 @@
+//! `prompt`: a one-line summary for shell prompts, e.g. "3 overdue · 5 today".
+//! It opens the database read-only and only runs an indexed count, so it
+//! stays fast enough to run on every prompt.
+
+use crate::db::DueCounts;
+use crate::encryption;
+use crate::query::DateValue;
+use crate::workflow::Workflow;
+use chrono::Local;
+use std::path::Path;
+
+const PLACEHOLDERS: [&str; 3] = ["overdue", "today", "week"];
+
+/// Fill in the `{overdue}`, `{today}` and `{week}` placeholders. The parts
+/// between separators whose counts are all zero are left out.
+fn render(format: &str, separator: &str, counts: DueCounts) -> Result<String, String> {
+    let parts: Vec<&str> = if separator.is_empty() {
+        vec![format]
+    } else {
+        format.split(separator).collect()
+    };
+    let mut shown = Vec::new();
+    for part in parts {
+        let mut text = String::new();
+        let mut any = false;
+        let mut rest = part;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed '{{' in \"{}\"", part))?;
+            let count = match &rest[start + 1..start + end] {
+                "overdue" => counts.overdue,
+                "today" => counts.today,
+                "week" => counts.week,
+                name => {
+                    return Err(format!(
+                        "unknown placeholder {{{}}} ({})",
+                        name,
+                        PLACEHOLDERS.join(", ")
+                    ))
+                }
+            };
+            any |= count > 0;
+            text.push_str(&rest[..start]);
+            text.push_str(&count.to_string());
+            rest = &rest[start + end + 1..];
+        }
+        text.push_str(rest);
+        if any || !part.contains('{') {
+            shown.push(text);
+        }
+    }
+    Ok(shown.join(separator))
+}
+
+/// Print the summary for the database at `path`; nothing when it doesn't
+/// exist yet, hasn't been upgraded to this version's schema, or there is
+/// nothing to report
+pub fn print(
+    path: &Path,
+    workflow: &Workflow,
+    format: &str,
+    separator: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let mut db = encryption::open_read_only(path, false)?;
+    // Upgrading needs a write, which is left to the next regular start
+    if !db.is_current()? {
+        return Ok(());
+    }
+    db.set_workflow(workflow.clone());
+    let today = Local::now().date_naive();
+    let end_of_week = DateValue::EndOfWeek.resolve(today).unwrap_or(today);
+    let summary = render(format, separator, db.due_counts(today, end_of_week)?)?;
+    if !summary.is_empty() {
+        println!("{}", summary);
+    }
+    Ok(())
+}
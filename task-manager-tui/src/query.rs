@@ -0,0 +1,533 @@
+//! Filter language for the search bar, e.g.
+//! `status:todo priority:high cat:work due<2026-11-01 tag:urgent -status:completed "exact phrase"`.
+//!
+//! Terms are ANDed together and a leading `-` negates one. Bare words are
+//! matched fuzzily against the title and description; everything else
+//! compiles to a parameterised SQL `WHERE` clause over the tasks table.
+//! Archived and trashed tasks only match with `in:archive`, `in:trash` or
+//! `in:all`.
+
+use crate::db::Scope;
+use crate::workflow::Workflow;
+use chrono::{Datelike, Duration, NaiveDate, TimeDelta};
+use rusqlite::types::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn sql(self) -> &'static str {
+        match self {
+            Cmp::Eq => "=",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+        }
+    }
+}
+
+/// A date as written in the query. Relative forms stay relative so a saved
+/// query means the same thing tomorrow.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateValue {
+    Date(NaiveDate),
+    /// Days from today: `today` is 0, `tomorrow` 1, `+2w` 14
+    Relative(i64),
+    /// The coming Sunday (`eow`)
+    EndOfWeek,
+    /// No due date (`due:none`)
+    None,
+}
+
+impl DateValue {
+    pub fn resolve(&self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            DateValue::Date(date) => Some(*date),
+            DateValue::Relative(days) => {
+                TimeDelta::try_days(*days).and_then(|days| today.checked_add_signed(days))
+            }
+            DateValue::EndOfWeek => {
+                Some(today + Duration::days(6 - today.weekday().num_days_from_monday() as i64))
+            }
+            DateValue::None => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Status(String),
+    Priority(String),
+    Category(String),
+    Tag(String),
+    Due(Cmp, DateValue),
+    /// `in:archive`, `in:trash`, `in:all` or `in:active`
+    In(Scope),
+    /// `"exact phrase"` in the title or description
+    Phrase(String),
+    /// A bare word, matched fuzzily
+    Word(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub negated: bool,
+    pub term: Term,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub clauses: Vec<Clause>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// 1-based character column the error points at
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "col {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A raw token: where it starts, whether it was negated, and its text with
+/// quotes removed. `quoted` is true when the whole token was a quoted phrase.
+struct Token {
+    column: usize,
+    negated: bool,
+    text: String,
+    quoted: bool,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let column = i + 1;
+        let negated = chars[i] == '-' && chars.get(i + 1).is_some_and(|c| !c.is_whitespace());
+        if negated {
+            i += 1;
+        }
+        let quoted = chars[i] == '"';
+        let mut text = String::new();
+        let mut in_quotes = false;
+        let mut quote_start = i;
+        while i < chars.len() && (in_quotes || !chars[i].is_whitespace()) {
+            if chars[i] == '"' {
+                in_quotes = !in_quotes;
+                quote_start = i;
+            } else {
+                text.push(chars[i]);
+            }
+            i += 1;
+        }
+        if in_quotes {
+            return Err(ParseError {
+                column: quote_start + 1,
+                message: "unterminated quote".to_string(),
+            });
+        }
+        tokens.push(Token {
+            column,
+            negated,
+            text,
+            quoted,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Farthest a relative date may reach, about a century either way, so it
+/// always resolves to a date
+const MAX_OFFSET_DAYS: i64 = 36_525;
+
+/// A date as accepted after `due`: YYYY-MM-DD, today, tomorrow, yesterday,
+/// eow, +Nd/-Nd, +Nw/-Nw or none
+pub fn parse_date(value: &str) -> Option<DateValue> {
+    let lower = value.to_lowercase();
+    let relative = |n: &str, unit: i64| {
+        let days = n.parse::<i64>().ok()?.checked_mul(unit)?;
+        (days.abs() <= MAX_OFFSET_DAYS).then_some(DateValue::Relative(days))
+    };
+    match lower.as_str() {
+        "none" => Some(DateValue::None),
+        "today" => Some(DateValue::Relative(0)),
+        "tomorrow" => Some(DateValue::Relative(1)),
+        "yesterday" => Some(DateValue::Relative(-1)),
+        "eow" => Some(DateValue::EndOfWeek),
+        _ if lower.starts_with(['+', '-']) && lower.ends_with('d') => {
+            relative(&lower[..lower.len() - 1], 1)
+        }
+        _ if lower.starts_with(['+', '-']) && lower.ends_with('w') => {
+            relative(&lower[..lower.len() - 1], 7)
+        }
+        _ => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .map(DateValue::Date),
+    }
+}
+
+/// Split `field<op>value` at the first operator character
+fn split_field(text: &str) -> Option<(&str, Cmp, &str)> {
+    let at = text.find([':', '=', '<', '>'])?;
+    let (field, rest) = text.split_at(at);
+    let (cmp, value) = if let Some(value) = rest.strip_prefix("<=") {
+        (Cmp::Le, value)
+    } else if let Some(value) = rest.strip_prefix(">=") {
+        (Cmp::Ge, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Cmp::Lt, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Cmp::Gt, value)
+    } else {
+        (Cmp::Eq, &rest[1..])
+    };
+    Some((field, cmp, value))
+}
+
+pub fn parse(input: &str) -> Result<Query, ParseError> {
+    let mut clauses = Vec::new();
+    for token in tokenize(input)? {
+        let error = |message: String| ParseError {
+            column: token.column,
+            message,
+        };
+        let term = if token.quoted {
+            Term::Phrase(token.text.clone())
+        } else if let Some((field, cmp, value)) = split_field(&token.text) {
+            if value.is_empty() {
+                return Err(error(format!("missing value after '{}'", field)));
+            }
+            let field = field.to_lowercase();
+            if field != "due" && cmp != Cmp::Eq {
+                return Err(error(format!("'{}' only supports ':'", field)));
+            }
+            match field.as_str() {
+                "status" => Term::Status(value.to_string()),
+                "priority" | "pri" => Term::Priority(value.to_string()),
+                "category" | "cat" => Term::Category(value.to_string()),
+                "tag" => Term::Tag(value.to_string()),
+                "in" => Term::In(Scope::parse(value).ok_or_else(|| {
+                    error(format!(
+                        "unknown scope '{}' (use active, archive, trash or all)",
+                        value
+                    ))
+                })?),
+                "due" => match parse_date(value) {
+                    Some(DateValue::None) if cmp != Cmp::Eq => {
+                        return Err(error("'none' can only be used as due:none".to_string()))
+                    }
+                    Some(date) => Term::Due(cmp, date),
+                    None => {
+                        return Err(error(format!(
+                            "'{}' is not a date (use YYYY-MM-DD, today, tomorrow, eow or +3d)",
+                            value
+                        )))
+                    }
+                },
+                "" => return Err(error("missing field name".to_string())),
+                other => {
+                    return Err(error(format!(
+                        "unknown field '{}' (use status, priority, cat, due, tag or in)",
+                        other
+                    )))
+                }
+            }
+        } else {
+            Term::Word(token.text.clone())
+        };
+        clauses.push(Clause {
+            negated: token.negated,
+            term,
+        });
+    }
+    Ok(Query { clauses })
+}
+
+/// Lowercase and drop spaces, so `status:todo` matches "To Do"
+fn normalize(value: &str) -> String {
+    value.to_lowercase().replace(' ', "")
+}
+
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+impl Query {
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    /// Positive bare words, for the fuzzy matcher
+    pub fn fuzzy_text(&self) -> String {
+        self.clauses
+            .iter()
+            .filter_map(|clause| match &clause.term {
+                Term::Word(word) if !clause.negated => Some(word.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `WHERE` clause and parameters for everything except the fuzzy words.
+    /// `status:done` and `status:open` stand for all of the workflow's done
+    /// or other statuses, unless a status has that name.
+    pub fn to_sql(&self, today: NaiveDate, workflow: &Workflow) -> (String, Vec<Value>) {
+        let done: Vec<&str> = workflow
+            .statuses()
+            .iter()
+            .filter(|status| status.done)
+            .map(|status| status.name.as_str())
+            .collect();
+        let done = serde_json::to_string(&done).unwrap_or_default();
+        let mut conditions = Vec::new();
+        if !self
+            .clauses
+            .iter()
+            .any(|clause| matches!(clause.term, Term::In(_)))
+        {
+            conditions.push(Scope::Active.sql().to_string());
+        }
+        let mut params = Vec::new();
+        for clause in &self.clauses {
+            let condition = match &clause.term {
+                Term::Status(value)
+                    if workflow.choose(value).is_none()
+                        && matches!(normalize(value).as_str(), "done" | "open") =>
+                {
+                    params.push(Value::Text(done.clone()));
+                    format!(
+                        "status {}IN (SELECT value FROM json_each(?))",
+                        if normalize(value) == "open" {
+                            "NOT "
+                        } else {
+                            ""
+                        }
+                    )
+                }
+                Term::Status(value) => {
+                    params.push(Value::Text(normalize(value)));
+                    "lower(replace(status, ' ', '')) = ?".to_string()
+                }
+                Term::Priority(value) => {
+                    params.push(Value::Text(normalize(value)));
+                    "lower(replace(priority, ' ', '')) = ?".to_string()
+                }
+                Term::Category(value) => {
+                    params.push(Value::Text(normalize(value)));
+                    "lower(replace(category, ' ', '')) = ?".to_string()
+                }
+                Term::Tag(value) => {
+                    params.push(Value::Text(value.to_lowercase()));
+                    "EXISTS (SELECT 1 FROM task_tags WHERE task_tags.task_id = tasks.id AND lower(tag) = ?)"
+                        .to_string()
+                }
+                Term::Due(cmp, date) => match date.resolve(today) {
+                    Some(date) => {
+                        params.push(Value::Text(date.format("%Y-%m-%d").to_string()));
+                        format!(
+                            "(due_date IS NOT NULL AND due_date <> '' AND due_date {} ?)",
+                            cmp.sql()
+                        )
+                    }
+                    None => "(due_date IS NULL OR due_date = '')".to_string(),
+                },
+                Term::In(scope) => scope.sql().to_string(),
+                // Positive words are left to the fuzzy matcher
+                Term::Word(_) if !clause.negated => continue,
+                Term::Phrase(text) | Term::Word(text) => {
+                    params.push(Value::Text(like_pattern(text)));
+                    params.push(Value::Text(like_pattern(text)));
+                    "(title LIKE ? ESCAPE '\\' OR ifnull(description, '') LIKE ? ESCAPE '\\')"
+                        .to_string()
+                }
+            };
+            conditions.push(if clause.negated {
+                format!("NOT {}", condition)
+            } else {
+                condition
+            });
+        }
+        (conditions.join(" AND "), params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parses_date_forms() {
+        assert_eq!(parse_date("today"), Some(DateValue::Relative(0)));
+        assert_eq!(parse_date("Tomorrow"), Some(DateValue::Relative(1)));
+        assert_eq!(parse_date("yesterday"), Some(DateValue::Relative(-1)));
+        assert_eq!(parse_date("eow"), Some(DateValue::EndOfWeek));
+        assert_eq!(parse_date("none"), Some(DateValue::None));
+        assert_eq!(parse_date("+3d"), Some(DateValue::Relative(3)));
+        assert_eq!(parse_date("-2W"), Some(DateValue::Relative(-14)));
+        assert_eq!(
+            parse_date("2026-11-01"),
+            Some(DateValue::Date(date("2026-11-01")))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_dates() {
+        for value in ["3d", "+xd", "+d", "2026-13-01", "soon", ""] {
+            assert_eq!(parse_date(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_offsets_out_of_range() {
+        for value in [
+            "+100000000d",
+            "+99999999999w",
+            "+9223372036854775807d",
+            "-9223372036854775807w",
+            "+36526d",
+        ] {
+            assert_eq!(parse_date(value), None, "{}", value);
+        }
+        assert_eq!(parse_date("+36525d"), Some(DateValue::Relative(36_525)));
+    }
+
+    #[test]
+    fn resolves_dates() {
+        let wednesday = date("2026-10-14");
+        assert_eq!(
+            DateValue::Relative(3).resolve(wednesday),
+            Some(date("2026-10-17"))
+        );
+        assert_eq!(
+            DateValue::EndOfWeek.resolve(wednesday),
+            Some(date("2026-10-18"))
+        );
+        assert_eq!(
+            DateValue::EndOfWeek.resolve(date("2026-10-18")),
+            Some(date("2026-10-18"))
+        );
+        assert_eq!(DateValue::None.resolve(wednesday), None);
+        assert_eq!(DateValue::Relative(i64::MAX).resolve(wednesday), None);
+    }
+
+    #[test]
+    fn parses_terms() {
+        let query = parse(r#"status:todo -tag:home due<=+1w "exact phrase" milk"#).unwrap();
+        assert_eq!(
+            query.clauses,
+            vec![
+                Clause {
+                    negated: false,
+                    term: Term::Status("todo".to_string()),
+                },
+                Clause {
+                    negated: true,
+                    term: Term::Tag("home".to_string()),
+                },
+                Clause {
+                    negated: false,
+                    term: Term::Due(Cmp::Le, DateValue::Relative(7)),
+                },
+                Clause {
+                    negated: false,
+                    term: Term::Phrase("exact phrase".to_string()),
+                },
+                Clause {
+                    negated: false,
+                    term: Term::Word("milk".to_string()),
+                },
+            ]
+        );
+        assert_eq!(query.fuzzy_text(), "milk");
+    }
+
+    #[test]
+    fn reports_errors_with_columns() {
+        let error = |input: &str| parse(input).unwrap_err();
+        assert_eq!(error("milk due<+100000000d").column, 6);
+        assert!(error("due:someday").message.contains("is not a date"));
+        assert!(error("owner:me").message.contains("unknown field 'owner'"));
+        assert!(error("status<todo").message.contains("only supports ':'"));
+        assert!(error("due:").message.contains("missing value"));
+        assert!(error("due<none").message.contains("due:none"));
+        assert!(error("in:nowhere").message.contains("unknown scope"));
+        let quote = error(r#"milk "open"#);
+        assert_eq!(
+            (quote.column, quote.message.as_str()),
+            (6, "unterminated quote")
+        );
+    }
+
+    #[test]
+    fn compiles_to_sql() {
+        let today = date("2026-10-14");
+        let workflow = Workflow::default();
+        let (sql, params) = parse("status:done due<today")
+            .unwrap()
+            .to_sql(today, &workflow);
+        assert_eq!(
+            sql,
+            format!(
+                "{} AND status IN (SELECT value FROM json_each(?)) \
+                AND (due_date IS NOT NULL AND due_date <> '' AND due_date < ?)",
+                Scope::Active.sql()
+            )
+        );
+        assert_eq!(
+            params,
+            vec![
+                Value::Text(r#"["Completed"]"#.to_string()),
+                Value::Text("2026-10-14".to_string()),
+            ]
+        );
+
+        let (sql, params) = parse("in:trash -status:open -due:none")
+            .unwrap()
+            .to_sql(today, &workflow);
+        assert_eq!(
+            sql,
+            "trashed_at IS NOT NULL AND NOT status NOT IN (SELECT value FROM json_each(?)) \
+            AND NOT (due_date IS NULL OR due_date = '')"
+        );
+        assert_eq!(params.len(), 1);
+
+        let (sql, params) = parse("-milk cat:Career").unwrap().to_sql(today, &workflow);
+        assert!(sql.ends_with("AND NOT (title LIKE ? ESCAPE '\\' OR ifnull(description, '') LIKE ? ESCAPE '\\') AND lower(replace(category, ' ', '')) = ?"));
+        assert_eq!(
+            params,
+            vec![
+                Value::Text("%milk%".to_string()),
+                Value::Text("%milk%".to_string()),
+                Value::Text("career".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! A task typed on one line, e.g. `Pay rent #home !high @tomorrow 09:00 ^42`.
+//!
+//! `#` names a category, or adds a tag when it isn't one; `!` sets the
+//! priority, `@` the due date (anything `due:` accepts in a search), HH:MM
+//! the time and `^` the parent's id. The other words make the title.
+
+use crate::bulk::choose;
+use crate::db::{self, Database, Task};
+use crate::query::{self, DateValue};
+use crate::workflow::Workflow;
+use chrono::{NaiveDate, NaiveTime};
+
+pub fn parse(input: &str, today: NaiveDate, workflow: &Workflow) -> Result<Task, String> {
+    let mut task = Task {
+        category: "Personal".to_string(),
+        priority: "Medium".to_string(),
+        status: workflow.initial().to_string(),
+        ..Default::default()
+    };
+    let mut title = Vec::new();
+    for word in input.split_whitespace() {
+        let (sigil, value) = word.split_at(word.chars().next().map_or(0, char::len_utf8));
+        match sigil {
+            _ if value.is_empty() => title.push(word),
+            "#" => match choose(value, &db::CATEGORIES) {
+                Some(category) => task.category = category,
+                None => {
+                    if !task.tags.iter().any(|tag| tag == value) {
+                        task.tags.push(value.to_string());
+                    }
+                }
+            },
+            "!" => {
+                task.priority = choose(value, &db::PRIORITIES).ok_or_else(|| {
+                    format!(
+                        "unknown priority '{}' ({})",
+                        value,
+                        db::PRIORITIES.join(", ")
+                    )
+                })?
+            }
+            "@" => {
+                task.due_date = match query::parse_date(value) {
+                    Some(DateValue::None) => None,
+                    Some(date) => date
+                        .resolve(today)
+                        .map(|date| date.format("%Y-%m-%d").to_string()),
+                    None => return Err(format!("'{}' is not a date", value)),
+                }
+            }
+            "^" => {
+                task.parent_task_id = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("'^{}' is not a task id", value))?,
+                )
+            }
+            _ => match NaiveTime::parse_from_str(word, "%H:%M") {
+                Ok(time) => task.time = Some(time.format("%H:%M").to_string()),
+                Err(_) => title.push(word),
+            },
+        }
+    }
+    task.title = title.join(" ");
+    if task.title.is_empty() {
+        return Err("the task needs a title".to_string());
+    }
+    Ok(task)
+}
+
+/// The parsed fields, with `parent` being the task `^` refers to
+pub fn preview(task: &Task, parent: Option<&Task>) -> String {
+    let mut fields = vec![
+        format!("\"{}\"", task.title),
+        task.category.clone(),
+        task.priority.clone(),
+    ];
+    match (&task.due_date, &task.time) {
+        (Some(due), Some(time)) => fields.push(format!("due {} {}", due, time)),
+        (Some(due), None) => fields.push(format!("due {}", due)),
+        (None, Some(time)) => fields.push(format!("at {}", time)),
+        (None, None) => {}
+    }
+    if let Some(id) = task.parent_task_id {
+        fields.push(match parent {
+            Some(parent) => format!("under \"{}\"", parent.title),
+            None => format!("under missing task {}", id),
+        });
+    }
+    if !task.tags.is_empty() {
+        fields.push(
+            task.tags
+                .iter()
+                .map(|tag| format!("#{}", tag))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+    }
+    fields.join(" - ")
+}
+
+/// Save a parsed task, once its parent turns out to exist
+pub fn add(db: &Database, task: &Task) -> Result<i32, Box<dyn std::error::Error>> {
+    if let Some(parent) = task.parent_task_id {
+        if db.get_task(parent)?.is_none() {
+            return Err(format!("parent task {} does not exist", parent).into());
+        }
+    }
+    Ok(db.add_task(task)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 14).unwrap()
+    }
+
+    fn parse(input: &str) -> Result<Task, String> {
+        super::parse(input, today(), &Workflow::default())
+    }
+
+    #[test]
+    fn reads_every_token() {
+        let task = parse("Pay rent #home !high @tomorrow 09:00 ^42 #bills").unwrap();
+        assert_eq!(task.title, "Pay rent");
+        assert_eq!(task.category, "Home");
+        assert_eq!(task.priority, "High");
+        assert_eq!(task.due_date.as_deref(), Some("2026-10-15"));
+        assert_eq!(task.time.as_deref(), Some("09:00"));
+        assert_eq!(task.parent_task_id, Some(42));
+        assert_eq!(task.tags, vec!["bills"]);
+        assert_eq!(task.status, "To Do");
+    }
+
+    #[test]
+    fn keeps_defaults_and_plain_words() {
+        let task = parse("Call # mum ! about @ 5 #x #x").unwrap();
+        assert_eq!(task.title, "Call # mum ! about @ 5");
+        assert_eq!(task.category, "Personal");
+        assert_eq!(task.priority, "Medium");
+        assert_eq!(task.due_date, None);
+        assert_eq!(task.tags, vec!["x"]);
+        assert_eq!(parse("Read @none").unwrap().due_date, None);
+        assert_eq!(
+            parse("Ship @2026-11-02").unwrap().due_date.as_deref(),
+            Some("2026-11-02")
+        );
+        assert_eq!(parse("Meet 25:00").unwrap().title, "Meet 25:00");
+    }
+
+    #[test]
+    fn reports_bad_tokens() {
+        assert!(parse("x !urgent")
+            .unwrap_err()
+            .starts_with("unknown priority 'urgent'"));
+        assert_eq!(parse("x @someday").unwrap_err(), "'someday' is not a date");
+        assert_eq!(
+            parse("x @+100000000d").unwrap_err(),
+            "'+100000000d' is not a date"
+        );
+        assert_eq!(parse("x ^abc").unwrap_err(), "'^abc' is not a task id");
+        assert_eq!(
+            parse("#work !low 10:00").unwrap_err(),
+            "the task needs a title"
+        );
+    }
+
+    #[test]
+    fn previews_the_fields() {
+        let task = parse("Pay rent #home @tomorrow 09:00 ^42 #bills").unwrap();
+        assert_eq!(
+            preview(&task, None),
+            "\"Pay rent\" - Home - Medium - due 2026-10-15 09:00 - under missing task 42 - #bills"
+        );
+        let parent = Task {
+            title: "Flat".to_string(),
+            ..Default::default()
+        };
+        assert!(preview(&task, Some(&parent)).contains("under \"Flat\""));
+        assert_eq!(
+            preview(&parse("Nap 14:00").unwrap(), None),
+            "\"Nap\" - Personal - Medium - at 14:00"
+        );
+    }
+}
@@ -0,0 +1,546 @@
+use crate::db::{self, Database, Scope, Task};
+use chrono::{NaiveDate, NaiveTime};
+use serde_json::{json, Value};
+use std::io::Read;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const OPENAPI: &str = include_str!("../openapi.json");
+/// Largest request body accepted
+const MAX_BODY: usize = 1024 * 1024;
+
+/// An HTTP status plus JSON body
+type Reply = (u16, Value);
+
+fn error(status: u16, message: impl Into<String>) -> Reply {
+    (status, json!({ "error": message.into() }))
+}
+
+/// Serve the JSON API on 127.0.0.1 until the process is killed. When `token`
+/// is set every request must send `Authorization: Bearer <token>`.
+pub fn serve(
+    db: &Database,
+    port: u16,
+    token: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::http(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+    println!(
+        "Serving tasks on http://127.0.0.1:{} (Ctrl+C to stop)",
+        port
+    );
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = respond(db, &mut request, port, token);
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let body = if body.is_null() {
+            String::new()
+        } else {
+            body.to_string()
+        };
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(response) {
+            eprintln!("failed to send response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Check the Host, token and Content-Type before handing the request on
+fn respond(db: &Database, request: &mut Request, port: u16, token: Option<&str>) -> Reply {
+    if !local_host(request, port) {
+        error(403, "Host must be 127.0.0.1 or localhost with the port")
+    } else if !authorized(request, token) {
+        error(401, "missing or invalid bearer token")
+    } else if !json_content(request) {
+        error(415, "Content-Type must be application/json")
+    } else {
+        handle(db, request).unwrap_or_else(|e| error(500, e.to_string()))
+    }
+}
+
+fn authorized(request: &Request, token: Option<&str>) -> bool {
+    let token = match token {
+        Some(token) => token,
+        None => return true,
+    };
+    request.headers().iter().any(|header| {
+        header.field.equiv("Authorization")
+            && header.value.as_str().strip_prefix("Bearer ") == Some(token)
+    })
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// Whether the request was addressed to this server, rejecting pages that
+/// reach it through a DNS name rebound to 127.0.0.1
+fn local_host(request: &Request, port: u16) -> bool {
+    header(request, "Host").is_some_and(|host| {
+        let host = host.to_lowercase();
+        host == format!("127.0.0.1:{}", port) || host == format!("localhost:{}", port)
+    })
+}
+
+/// Writes must be JSON, which a web page can't send to another origin without
+/// a CORS preflight this server never answers
+fn json_content(request: &Request) -> bool {
+    !matches!(request.method(), Method::Post | Method::Put | Method::Patch)
+        || header(request, "Content-Type").is_some_and(|value| {
+            value
+                .split(';')
+                .next()
+                .is_some_and(|media| media.trim().eq_ignore_ascii_case("application/json"))
+        })
+}
+
+fn read_json(request: &mut Request) -> Result<Value, Reply> {
+    let too_large = || error(413, format!("the body must be at most {} bytes", MAX_BODY));
+    if request
+        .body_length()
+        .is_some_and(|length| length > MAX_BODY)
+    {
+        return Err(too_large());
+    }
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY as u64 + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| error(400, e.to_string()))?;
+    if body.len() > MAX_BODY {
+        return Err(too_large());
+    }
+    let value: Value =
+        serde_json::from_str(&body).map_err(|e| error(400, format!("invalid JSON: {}", e)))?;
+    if value.is_object() {
+        Ok(value)
+    } else {
+        Err(error(400, "expected a JSON object"))
+    }
+}
+
+/// Overlay the fields present in `patch` onto `task`; id, uuid and
+/// timestamps are kept
+fn apply_patch(task: &Task, patch: &Value) -> Result<Task, Reply> {
+    let mut merged = serde_json::to_value(task).map_err(|e| error(500, e.to_string()))?;
+    for (key, value) in patch.as_object().into_iter().flatten() {
+        if !matches!(
+            key.as_str(),
+            "id" | "uuid" | "updated_at" | "created_at" | "archived_at" | "trashed_at"
+        ) {
+            merged[key] = value.clone();
+        }
+    }
+    serde_json::from_value(merged).map_err(|e| error(400, e.to_string()))
+}
+
+/// `old_status` is the stored task's, None for a new task
+fn validate(db: &Database, task: &Task, old_status: Option<&str>) -> Result<(), Reply> {
+    if task.title.trim().is_empty() {
+        return Err(error(422, "title must not be empty"));
+    }
+    if let Some(due) = &task.due_date {
+        if NaiveDate::parse_from_str(due, "%Y-%m-%d").is_err() {
+            return Err(error(422, format!("due_date '{}' is not YYYY-MM-DD", due)));
+        }
+    }
+    if let Some(time) = &task.time {
+        if NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+            return Err(error(422, format!("time '{}' is not HH:MM", time)));
+        }
+    }
+    if !db::PRIORITIES.contains(&task.priority.as_str()) {
+        return Err(error(
+            422,
+            format!(
+                "unknown priority '{}' ({})",
+                task.priority,
+                db::PRIORITIES.join(", ")
+            ),
+        ));
+    }
+    check_status(db, old_status, &task.status)?;
+    if let Some(parent) = task.parent_task_id {
+        let descendants = db
+            .descendant_ids(task.id)
+            .map_err(|e| error(500, e.to_string()))?;
+        if parent == task.id || descendants.contains(&parent) {
+            return Err(error(422, "a task can't be nested under itself"));
+        }
+        if db
+            .get_task(parent)
+            .map_err(|e| error(500, e.to_string()))?
+            .is_none()
+        {
+            return Err(error(422, format!("parent task {} does not exist", parent)));
+        }
+    }
+    Ok(())
+}
+
+/// An unknown status is unprocessable; one the workflow doesn't allow from
+/// the current status is a conflict
+fn check_status(db: &Database, from: Option<&str>, to: &str) -> Result<(), Reply> {
+    let workflow = db.workflow();
+    if workflow.get(to).is_none() {
+        return Err(error(
+            422,
+            format!("unknown status '{}' ({})", to, workflow.names().join(", ")),
+        ));
+    }
+    match from.map(|from| workflow.check(from, to)) {
+        Some(Err(e)) => Err(error(409, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Query string `a=1&b=2` as pairs; values are percent-decoded
+fn query_pairs(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// `GET /tasks` filters: status, category, priority, parent (an id or
+/// "none" for top-level tasks) and q (case-insensitive title substring).
+/// `in` picks the scope and is applied when loading.
+fn matches_filters(task: &Task, filters: &[(String, String)]) -> Result<bool, Reply> {
+    for (key, value) in filters {
+        let matched = match key.as_str() {
+            "status" => task.status == *value,
+            "category" => task.category == *value,
+            "priority" => task.priority == *value,
+            "parent" if value == "none" => task.parent_task_id.is_none(),
+            "parent" => {
+                let parent: i32 = value
+                    .parse()
+                    .map_err(|_| error(400, "parent must be an id or \"none\""))?;
+                task.parent_task_id == Some(parent)
+            }
+            "q" => task.title.to_lowercase().contains(&value.to_lowercase()),
+            "in" => true,
+            other => return Err(error(400, format!("unknown filter '{}'", other))),
+        };
+        if !matched {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn handle(db: &Database, request: &mut Request) -> Result<Reply, Box<dyn std::error::Error>> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let method = request.method().clone();
+
+    let id = match segments.get(1).filter(|_| segments[0] == "tasks") {
+        Some(id) => match id.parse::<i32>() {
+            Ok(id) => Some(id),
+            Err(_) => return Ok(error(404, "not found")),
+        },
+        None => None,
+    };
+    let task = match id {
+        Some(id) => match db.get_task(id)? {
+            Some(task) => Some(task),
+            None => return Ok(error(404, format!("task {} not found", id))),
+        },
+        None => None,
+    };
+
+    let reply = match (&method, segments.as_slice(), task) {
+        (Method::Get, ["openapi.json"], _) => (200, serde_json::from_str(OPENAPI)?),
+
+        (Method::Get, ["tasks"], _) => {
+            let filters = query_pairs(query);
+            let scope = match filters.iter().find(|(key, _)| key == "in") {
+                Some((_, value)) => match Scope::parse(value) {
+                    Some(scope) => scope,
+                    None => return Ok(error(400, "in must be active, archive, trash or all")),
+                },
+                None => Scope::Active,
+            };
+            let mut tasks = Vec::new();
+            for task in db.get_tasks(scope)? {
+                match matches_filters(&task, &filters) {
+                    Ok(true) => tasks.push(task),
+                    Ok(false) => {}
+                    Err(reply) => return Ok(reply),
+                }
+            }
+            (200, serde_json::to_value(tasks)?)
+        }
+
+        (Method::Post, ["tasks"], _) => {
+            let new_task =
+                match read_json(request).and_then(|body| apply_patch(&default_task(db), &body)) {
+                    Ok(task) => task,
+                    Err(reply) => return Ok(reply),
+                };
+            if let Err(reply) = validate(db, &new_task, None) {
+                return Ok(reply);
+            }
+            let id = db.add_task(&new_task)?;
+            (201, serde_json::to_value(db.get_task(id)?)?)
+        }
+
+        (Method::Get, ["tasks", _], Some(task)) => (200, serde_json::to_value(task)?),
+
+        (Method::Put | Method::Patch, ["tasks", _], Some(task)) => {
+            // PUT replaces every field, PATCH only the ones sent
+            let old_status = task.status.clone();
+            let base = if method == Method::Put {
+                Task {
+                    id: task.id,
+                    uuid: task.uuid.clone(),
+                    ..default_task(db)
+                }
+            } else {
+                task
+            };
+            let updated = match read_json(request).and_then(|body| apply_patch(&base, &body)) {
+                Ok(task) => task,
+                Err(reply) => return Ok(reply),
+            };
+            if let Err(reply) = validate(db, &updated, Some(&old_status)) {
+                return Ok(reply);
+            }
+            db.update_task(&updated)?;
+            (200, serde_json::to_value(db.get_task(updated.id)?)?)
+        }
+
+        // Deleting moves the task to the trash; deleting it there is final
+        (Method::Delete, ["tasks", _], Some(task)) => {
+            if task.trashed_at.is_some() {
+                db.delete_task(task.id)?;
+            } else {
+                db.trash_task(task.id)?;
+            }
+            (204, Value::Null)
+        }
+
+        (Method::Post, ["tasks", _, "restore"], Some(task)) => {
+            db.restore_task(task.id)?;
+            (200, serde_json::to_value(db.get_task(task.id)?)?)
+        }
+
+        (Method::Get, ["tasks", _, "subtasks"], Some(task)) => {
+            let subtasks: Vec<Task> = db
+                .get_all_tasks()?
+                .into_iter()
+                .filter(|t| t.parent_task_id == Some(task.id))
+                .collect();
+            (200, serde_json::to_value(subtasks)?)
+        }
+
+        (Method::Put, ["tasks", _, "status"], Some(task)) => {
+            let body = match read_json(request) {
+                Ok(body) => body,
+                Err(reply) => return Ok(reply),
+            };
+            match body.get("status").and_then(Value::as_str) {
+                Some(status) => {
+                    if let Err(reply) = check_status(db, Some(&task.status), status) {
+                        return Ok(reply);
+                    }
+                    db.set_status(task.id, status)?;
+                    (200, serde_json::to_value(db.get_task(task.id)?)?)
+                }
+                None => error(422, "expected {\"status\": \"...\"}"),
+            }
+        }
+
+        _ => error(404, "not found"),
+    };
+    Ok(reply)
+}
+
+/// Field defaults for tasks created through the API, matching the TUI dialog
+fn default_task(db: &Database) -> Task {
+    Task {
+        category: "Personal".to_string(),
+        priority: "Medium".to_string(),
+        status: db.workflow().initial().to_string(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::{Status, Workflow};
+    use tiny_http::TestRequest;
+
+    const PORT: u16 = 8080;
+
+    fn database() -> Database {
+        let db = Database::new(":memory:").unwrap();
+        db.migrate().unwrap();
+        db
+    }
+
+    #[test]
+    fn rejects_malformed_fields() {
+        let db = database();
+        let valid = Task {
+            title: "Write report".to_string(),
+            due_date: Some("2026-10-20".to_string()),
+            time: Some("09:30".to_string()),
+            ..default_task(&db)
+        };
+        assert!(validate(&db, &valid, None).is_ok());
+        let status = |task: Task| validate(&db, &task, None).map_err(|(status, _)| status);
+        assert_eq!(
+            status(Task {
+                due_date: Some("next tuesday".to_string()),
+                ..valid.clone()
+            }),
+            Err(422)
+        );
+        assert_eq!(
+            status(Task {
+                time: Some("25:99".to_string()),
+                ..valid.clone()
+            }),
+            Err(422)
+        );
+        assert_eq!(
+            status(Task {
+                priority: "urgent".to_string(),
+                ..valid.clone()
+            }),
+            Err(422)
+        );
+    }
+
+    /// A request to the test server on `PORT`; `headers` replace the valid
+    /// Host, Authorization and Content-Type defaults, and an empty value
+    /// leaves the header out
+    fn request(
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &'static str,
+    ) -> Request {
+        let defaults = [
+            ("Host", "127.0.0.1:8080"),
+            ("Authorization", "Bearer secret"),
+            ("Content-Type", "application/json"),
+        ];
+        let mut request = TestRequest::new()
+            .with_method(method)
+            .with_path(path)
+            .with_body(body);
+        for (field, default) in defaults {
+            let value = headers
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map_or(default, |(_, value)| value);
+            if !value.is_empty() {
+                request = request.with_header(Header::from_bytes(field, value).unwrap());
+            }
+        }
+        request.into()
+    }
+
+    fn status(db: &Database, mut request: Request) -> u16 {
+        respond(db, &mut request, PORT, Some("secret")).0
+    }
+
+    #[test]
+    fn checks_host_token_and_content_type() {
+        let db = database();
+        let get = |headers| status(&db, request(Method::Get, "/tasks", headers, ""));
+        assert_eq!(get(&[]), 200);
+        assert_eq!(get(&[("Host", "localhost:8080")]), 200);
+        assert_eq!(get(&[("Host", "")]), 403);
+        assert_eq!(get(&[("Host", "rebound.example:8080")]), 403);
+        assert_eq!(get(&[("Host", "127.0.0.1:9090")]), 403);
+        assert_eq!(get(&[("Authorization", "")]), 401);
+        assert_eq!(get(&[("Authorization", "Bearer wrong")]), 401);
+
+        let body = r#"{"title": "Write report"}"#;
+        let post = |headers| status(&db, request(Method::Post, "/tasks", headers, body));
+        assert_eq!(post(&[("Content-Type", "text/plain")]), 415);
+        assert_eq!(post(&[("Content-Type", "")]), 415);
+        assert_eq!(
+            post(&[("Content-Type", "application/json; charset=utf-8")]),
+            201
+        );
+    }
+
+    #[test]
+    fn status_changes_follow_the_workflow() {
+        let mut db = database();
+        let status_named = |name: &str, from: Option<&str>| Status {
+            name: name.to_string(),
+            color: None,
+            done: name == "Done",
+            from: from.map(|from| vec![from.to_string()]),
+        };
+        db.set_workflow(
+            Workflow::new(vec![
+                status_named("Doing", None),
+                status_named("Review", None),
+                status_named("Done", Some("Review")),
+            ])
+            .unwrap(),
+        );
+        let id = db
+            .add_task(&Task {
+                title: "Write report".to_string(),
+                ..default_task(&db)
+            })
+            .unwrap();
+        let path = format!("/tasks/{}/status", id);
+        let put = |body| status(&db, request(Method::Put, &path, &[], body));
+        assert_eq!(put(r#"{"status": "Done"}"#), 409);
+        assert_eq!(put(r#"{"status": "Shipped"}"#), 422);
+        assert_eq!(put(r#"{"status": "Review"}"#), 200);
+        assert_eq!(put(r#"{"status": "Done"}"#), 200);
+        assert_eq!(db.get_task(id).unwrap().unwrap().status, "Done");
+        assert_eq!(
+            status(
+                &db,
+                request(Method::Post, &path, &[], r#"{"status": "Doing"}"#)
+            ),
+            404
+        );
+    }
+}
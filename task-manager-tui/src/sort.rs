@@ -0,0 +1,159 @@
+use crate::db::Task;
+use crate::workflow::Workflow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    Id,
+    Due,
+    Priority,
+    Title,
+    Status,
+    Category,
+    /// The computed urgency score (see `urgency`)
+    Urgency,
+}
+
+/// One key of a sort spec like "due,priority,-title"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+/// Parse a comma-separated sort spec; a leading `-` sorts that key
+/// descending, so "-urgency" puts the most urgent task first
+pub fn parse_sort(spec: &str) -> Result<Vec<SortKey>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            let (descending, name) = match key.strip_prefix('-') {
+                Some(name) => (true, name),
+                None => (false, key),
+            };
+            let field = match name.to_lowercase().as_str() {
+                "id" => SortField::Id,
+                "due" => SortField::Due,
+                "priority" | "pri" => SortField::Priority,
+                "title" => SortField::Title,
+                "status" => SortField::Status,
+                "category" | "cat" => SortField::Category,
+                "urgency" => SortField::Urgency,
+                other => return Err(format!("unknown sort key '{}'", other)),
+            };
+            Ok(SortKey { field, descending })
+        })
+        .collect()
+}
+
+/// High first, then Medium, Low and anything else
+pub fn priority_rank(priority: &str) -> u8 {
+    match priority {
+        "High" => 0,
+        "Medium" => 1,
+        "Low" => 2,
+        _ => 3,
+    }
+}
+
+fn compare_field(
+    a: &Task,
+    b: &Task,
+    field: SortField,
+    urgency: &HashMap<i32, f64>,
+    workflow: &Workflow,
+) -> Ordering {
+    match field {
+        SortField::Id => a.id.cmp(&b.id),
+        // Tasks without a due date go last
+        SortField::Due => {
+            let due = |t: &Task| t.due_date.clone().filter(|d| !d.is_empty());
+            match (due(a), due(b)) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+        SortField::Priority => priority_rank(&a.priority).cmp(&priority_rank(&b.priority)),
+        SortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+        // In workflow order
+        SortField::Status => workflow.rank(&a.status).cmp(&workflow.rank(&b.status)),
+        SortField::Category => a.category.cmp(&b.category),
+        SortField::Urgency => {
+            let score = |t: &Task| urgency.get(&t.id).copied().unwrap_or_default();
+            score(a).total_cmp(&score(b))
+        }
+    }
+}
+
+/// Compare by each key in turn, then by id. `urgency` holds the scores for
+/// `SortField::Urgency`.
+pub fn compare(
+    a: &Task,
+    b: &Task,
+    keys: &[SortKey],
+    urgency: &HashMap<i32, f64>,
+    workflow: &Workflow,
+) -> Ordering {
+    keys.iter()
+        .map(|key| {
+            let ordering = compare_field(a, b, key.field, urgency, workflow);
+            if key.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.id.cmp(&b.id))
+}
+
+/// How a saved view splits its tasks under headers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    None,
+    Category,
+    Status,
+    Priority,
+}
+
+impl GroupBy {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(GroupBy::None),
+            "category" | "cat" => Ok(GroupBy::Category),
+            "status" => Ok(GroupBy::Status),
+            "priority" | "pri" => Ok(GroupBy::Priority),
+            other => Err(format!("unknown grouping '{}'", other)),
+        }
+    }
+
+    /// Header the task is listed under
+    pub fn label(self, task: &Task) -> Option<String> {
+        let value = match self {
+            GroupBy::None => return None,
+            GroupBy::Category => &task.category,
+            GroupBy::Status => &task.status,
+            GroupBy::Priority => &task.priority,
+        };
+        Some(if value.is_empty() {
+            "(none)".to_string()
+        } else {
+            value.clone()
+        })
+    }
+
+    /// Statuses and priorities are grouped in their own order, the rest by
+    /// name
+    pub fn compare(self, a: &Task, b: &Task, workflow: &Workflow) -> Ordering {
+        match self {
+            GroupBy::None => Ordering::Equal,
+            GroupBy::Priority => priority_rank(&a.priority).cmp(&priority_rank(&b.priority)),
+            GroupBy::Status => workflow.rank(&a.status).cmp(&workflow.rank(&b.status)),
+            _ => self.label(a).cmp(&self.label(b)),
+        }
+    }
+}
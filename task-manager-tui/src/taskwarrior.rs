@@ -0,0 +1,396 @@
+//! `import-taskwarrior`: tasks from the JSON of Taskwarrior's `task export`.
+//! Re-importing updates the tasks imported before, matched by uuid.
+
+use crate::db::{self, Database, Scope, SyncRecord, Task, TRACKED_FIELDS};
+use crate::workflow::Workflow;
+use chrono::{DateTime, Local, NaiveDateTime, SecondsFormat, Timelike, Utc};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+
+/// Fields read into tasks, plus `id` and `urgency`, which Taskwarrior derives
+const MAPPED: [&str; 15] = [
+    "uuid",
+    "description",
+    "project",
+    "tags",
+    "priority",
+    "due",
+    "status",
+    "depends",
+    "entry",
+    "modified",
+    "start",
+    "end",
+    "annotations",
+    "id",
+    "urgency",
+];
+
+#[derive(Default)]
+struct Report {
+    imported: usize,
+    updated: usize,
+    /// Field name -> number of tasks that had it
+    unmapped: BTreeMap<String, usize>,
+    problems: Vec<String>,
+}
+
+/// Parse a Taskwarrior date, e.g. "20261018T120000Z"
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+        .map(|time| time.and_utc())
+        .ok()
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|time| time.with_timezone(&Utc))
+        })
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// `task export` prints a JSON array; before 2.6 it printed one object per
+/// line
+fn parse_export(text: &str) -> Result<Vec<Map<String, Value>>, String> {
+    let text = text.trim();
+    if text.starts_with('[') {
+        return serde_json::from_str(text).map_err(|e| e.to_string());
+    }
+    text.lines()
+        .map(|line| line.trim().trim_end_matches(','))
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e)))
+        .collect()
+}
+
+/// `depends` is a comma separated string before Taskwarrior 3 and an array
+/// since
+fn depends(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(uuids) => uuids
+            .split(',')
+            .map(|uuid| uuid.trim().to_string())
+            .filter(|uuid| !uuid.is_empty())
+            .collect(),
+        Value::Array(uuids) => uuids
+            .iter()
+            .filter_map(|uuid| uuid.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The task an exported object describes, or None for a recurring template,
+/// whose instances are exported as tasks of their own
+fn convert(
+    object: &Map<String, Value>,
+    workflow: &Workflow,
+    report: &mut Report,
+) -> Option<SyncRecord> {
+    let text = |field: &str| object.get(field).and_then(Value::as_str);
+    let title = text("description").unwrap_or_default().to_string();
+    let status = text("status").unwrap_or("pending");
+    if status == "recurring" {
+        report
+            .problems
+            .push(format!("\"{}\": skipped the recurring template", title));
+        return None;
+    }
+    for field in object.keys() {
+        if !MAPPED.contains(&field.as_str()) {
+            *report.unmapped.entry(field.clone()).or_default() += 1;
+        }
+    }
+    let mut time = |field: &str| {
+        let value = text(field)?;
+        let time = parse_time(value);
+        if time.is_none() {
+            report.problems.push(format!(
+                "\"{}\": {} \"{}\" is not a date",
+                title, field, value
+            ));
+        }
+        time
+    };
+    let modified = time("modified");
+    let entry = time("entry");
+    let end = time("end");
+    let due = time("due");
+    let updated_at = format_time(modified.or(end).or(entry).unwrap_or_else(Utc::now));
+
+    let mut task = Task {
+        uuid: text("uuid")
+            .map(String::from)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        title: title.clone(),
+        category: text("project").unwrap_or("Personal").to_string(),
+        priority: match text("priority") {
+            Some("H") => "High",
+            Some("L") => "Low",
+            _ => "Medium",
+        }
+        .to_string(),
+        status: match status {
+            "completed" => workflow.completed(),
+            _ if object.contains_key("start") => workflow.started(),
+            _ => workflow.initial(),
+        }
+        .to_string(),
+        created_at: entry.map(format_time).unwrap_or_default(),
+        updated_at: updated_at.clone(),
+        ..Default::default()
+    };
+    if let Some(priority) = text("priority").filter(|p| !["H", "M", "L"].contains(p)) {
+        report.problems.push(format!(
+            "\"{}\": priority \"{}\" is not H, M or L",
+            title, priority
+        ));
+    }
+    if status == "deleted" {
+        task.trashed_at = Some(end.map(format_time).unwrap_or_else(|| updated_at.clone()));
+    }
+    if let Some(due) = due {
+        // Dates without a time of day are midnight local time
+        let due = due.with_timezone(&Local);
+        task.due_date = Some(due.format("%Y-%m-%d").to_string());
+        if (due.hour(), due.minute()) != (0, 0) {
+            task.time = Some(due.format("%H:%M").to_string());
+        }
+    }
+    if let Some(Value::Array(tags)) = object.get("tags") {
+        let tags: Vec<&str> = tags.iter().filter_map(Value::as_str).collect();
+        task.tags = db::parse_tags(&tags.join(" "));
+    }
+    if let Some(Value::Array(annotations)) = object.get("annotations") {
+        task.description = annotations
+            .iter()
+            .filter_map(|annotation| annotation.get("description")?.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    Some(SyncRecord {
+        field_times: TRACKED_FIELDS
+            .iter()
+            .map(|field| (field.to_string(), updated_at.clone()))
+            .collect(),
+        task,
+        parent_uuid: None,
+    })
+}
+
+/// Import the export at `path` ("-" for stdin) and print what couldn't be
+/// mapped
+pub fn import(db: &Database, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut text = String::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut text)?;
+    } else {
+        text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let objects = parse_export(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let mut report = Report::default();
+    let existing: HashSet<String> = db
+        .get_tasks(Scope::All)?
+        .into_iter()
+        .map(|task| task.uuid)
+        .collect();
+    let mut dependencies: Vec<(String, String, Vec<String>)> = Vec::new();
+    db.transaction(|db| -> Result<(), Box<dyn std::error::Error>> {
+        for object in &objects {
+            let Some(record) = convert(object, db.workflow(), &mut report) else {
+                continue;
+            };
+            if existing.contains(&record.task.uuid) {
+                report.updated += 1;
+            } else {
+                report.imported += 1;
+            }
+            db.upsert_sync_record(&record)?;
+            // Also for tasks without any, to drop those removed since the
+            // last import
+            dependencies.push((
+                record.task.uuid.clone(),
+                record.task.title.clone(),
+                object.get("depends").map(depends).unwrap_or_default(),
+            ));
+        }
+
+        let ids: HashMap<String, i32> = db
+            .get_tasks(Scope::All)?
+            .into_iter()
+            .map(|task| (task.uuid, task.id))
+            .collect();
+        for (uuid, title, depends) in &dependencies {
+            let mut depends_on = Vec::new();
+            for other in depends {
+                match ids.get(other) {
+                    Some(&id) => depends_on.push(id),
+                    None => report.problems.push(format!(
+                        "\"{}\": depends on {}, which isn't in the export",
+                        title, other
+                    )),
+                }
+            }
+            db.set_dependencies(ids[uuid], &depends_on)?;
+        }
+        Ok(())
+    })?;
+
+    println!(
+        "Imported {} task(s), updated {} imported before",
+        report.imported, report.updated
+    );
+    if !report.unmapped.is_empty() {
+        println!("Fields with no counterpart, left out:");
+        for (field, count) in &report.unmapped {
+            println!("  {:<12} {} task(s)", field, count);
+        }
+    }
+    for problem in &report.problems {
+        println!("{}", problem);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    fn convert(value: Value, report: &mut Report) -> Option<SyncRecord> {
+        super::convert(&object(value), &Workflow::default(), report)
+    }
+
+    #[test]
+    fn parses_times() {
+        let time = parse_time("20261018T120000Z").unwrap();
+        assert_eq!(format_time(time), "2026-10-18T12:00:00.000Z");
+        assert_eq!(
+            parse_time("2026-10-18T14:00:00+02:00"),
+            Some(time),
+            "RFC 3339 is accepted too"
+        );
+        assert_eq!(parse_time("yesterday"), None);
+    }
+
+    #[test]
+    fn parses_both_export_formats() {
+        let array = parse_export(r#" [{"description": "a"}, {"description": "b"}] "#).unwrap();
+        let lines =
+            parse_export("{\"description\": \"a\"},\n\n{\"description\": \"b\"}\n").unwrap();
+        assert_eq!(array, lines);
+        assert_eq!(array.len(), 2);
+        assert!(parse_export("{\"a\": 1}\n{oops}")
+            .unwrap_err()
+            .starts_with("line 2:"));
+    }
+
+    #[test]
+    fn reads_depends_strings_and_arrays() {
+        assert_eq!(depends(&json!("a, b,,c")), ["a", "b", "c"]);
+        assert_eq!(depends(&json!(["a", 1, "b"])), ["a", "b"]);
+        assert!(depends(&json!(7)).is_empty());
+    }
+
+    #[test]
+    fn maps_fields() {
+        let mut report = Report::default();
+        let record = convert(
+            json!({
+                "uuid": "5d1b4c1c-0000-4000-8000-000000000001",
+                "description": "Write report",
+                "project": "Work",
+                "priority": "H",
+                "status": "pending",
+                "start": "20261017T080000Z",
+                "entry": "20261001T080000Z",
+                "modified": "20261016T080000Z",
+                "due": "20261020T153000Z",
+                "tags": ["office", "q4"],
+                "annotations": [{"description": "draft"}, {"description": "final"}],
+                "urgency": 9.1,
+                "id": 3,
+                "wait": "20261019T000000Z"
+            }),
+            &mut report,
+        )
+        .unwrap();
+        let task = &record.task;
+        assert_eq!(task.uuid, "5d1b4c1c-0000-4000-8000-000000000001");
+        assert_eq!(task.title, "Write report");
+        assert_eq!(task.category, "Work");
+        assert_eq!(task.priority, "High");
+        assert_eq!(task.status, "In Progress");
+        assert_eq!(task.created_at, "2026-10-01T08:00:00.000Z");
+        assert_eq!(task.updated_at, "2026-10-16T08:00:00.000Z");
+        let due = parse_time("20261020T153000Z")
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(task.due_date, Some(due.format("%Y-%m-%d").to_string()));
+        assert_eq!(task.time, Some(due.format("%H:%M").to_string()));
+        assert_eq!(task.tags, ["office", "q4"]);
+        assert_eq!(task.description, "draft\nfinal");
+        assert_eq!(task.trashed_at, None);
+        assert_eq!(record.field_times["status"], task.updated_at);
+        assert_eq!(report.unmapped.len(), 1);
+        assert_eq!(report.unmapped["wait"], 1);
+        assert!(report.problems.is_empty());
+    }
+
+    #[test]
+    fn maps_statuses() {
+        let mut report = Report::default();
+        let mut status = |value: Value| convert(value, &mut report).map(|r| r.task);
+        let completed = status(json!({"description": "a", "status": "completed"})).unwrap();
+        assert_eq!(completed.status, "Completed");
+        let deleted = status(json!({
+            "description": "b",
+            "status": "deleted",
+            "end": "20261018T120000Z"
+        }))
+        .unwrap();
+        assert_eq!(deleted.status, "To Do");
+        assert_eq!(
+            deleted.trashed_at.as_deref(),
+            Some("2026-10-18T12:00:00.000Z")
+        );
+        let plain = status(json!({"description": "c", "priority": "L"})).unwrap();
+        assert_eq!(
+            (plain.status.as_str(), plain.priority.as_str()),
+            ("To Do", "Low")
+        );
+        assert_eq!(plain.category, "Personal");
+        assert!(status(json!({"description": "d", "status": "recurring"})).is_none());
+        assert_eq!(report.problems, ["\"d\": skipped the recurring template"]);
+    }
+
+    #[test]
+    fn reports_bad_values() {
+        let mut report = Report::default();
+        let task = convert(
+            json!({"description": "x", "priority": "urgent", "due": "someday"}),
+            &mut report,
+        )
+        .unwrap()
+        .task;
+        assert_eq!(task.priority, "Medium");
+        assert_eq!(task.due_date, None);
+        assert_eq!(
+            report.problems,
+            [
+                "\"x\": due \"someday\" is not a date",
+                "\"x\": priority \"urgent\" is not H, M or L"
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Task templates: TOML files in `templates/` next to config.toml, each
+//! describing a parent task with nested sub-tasks. Due dates are offsets
+//! like `+3d` from the day the template is used.
+
+use crate::db::{self, Database, Task};
+use crate::query::{self, DateValue};
+use chrono::{NaiveDate, NaiveTime};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// A task of a template. Category and priority default to the parent's.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateTask {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub category: Option<String>,
+    pub priority: Option<String>,
+    /// Anything `due:` accepts in a search; offsets count from the start date
+    pub due: Option<String>,
+    pub time: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub estimate: Option<f64>,
+    #[serde(default)]
+    pub subtasks: Vec<TemplateTask>,
+}
+
+pub struct Template {
+    /// The file name without `.toml`
+    pub name: String,
+    pub task: TemplateTask,
+}
+
+impl TemplateTask {
+    fn check(&self) -> Result<(), String> {
+        let problem = |problem: String| Err(format!("\"{}\": {}", self.title, problem));
+        if self.title.trim().is_empty() {
+            return Err("a task has no title".to_string());
+        }
+        if let Some(due) = &self.due {
+            if query::parse_date(due).is_none() {
+                return problem(format!("due '{}' is not a date or offset", due));
+            }
+        }
+        if let Some(time) = &self.time {
+            if NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+                return problem(format!("time '{}' is not HH:MM", time));
+            }
+        }
+        if let Some(priority) = &self.priority {
+            if !db::PRIORITIES.contains(&priority.as_str()) {
+                return problem(format!(
+                    "priority '{}' is not one of {}",
+                    priority,
+                    db::PRIORITIES.join(", ")
+                ));
+            }
+        }
+        if self.estimate.is_some_and(|e| !e.is_finite() || e < 0.0) {
+            return problem("estimate must be a positive number".to_string());
+        }
+        self.subtasks.iter().try_for_each(TemplateTask::check)
+    }
+
+    fn count(&self) -> usize {
+        1 + self.subtasks.iter().map(TemplateTask::count).sum::<usize>()
+    }
+
+    fn add(
+        &self,
+        db: &Database,
+        parent: Option<(i32, &Task)>,
+        start: NaiveDate,
+    ) -> rusqlite::Result<i32> {
+        let inherited = |own: &Option<String>, field: fn(&Task) -> &String, default: &str| {
+            own.clone().unwrap_or_else(|| {
+                parent.map_or(default.to_string(), |(_, task)| field(task).clone())
+            })
+        };
+        let task = Task {
+            title: self.title.clone(),
+            description: self.description.clone(),
+            category: inherited(&self.category, |task| &task.category, "Personal"),
+            priority: inherited(&self.priority, |task| &task.priority, "Medium"),
+            parent_task_id: parent.map(|(id, _)| id),
+            due_date: self
+                .due
+                .as_deref()
+                .and_then(query::parse_date)
+                .and_then(|due| due.resolve(start))
+                .map(|due| due.format("%Y-%m-%d").to_string()),
+            time: self.time.clone(),
+            status: db.workflow().initial().to_string(),
+            tags: db::parse_tags(&self.tags.join(" ")),
+            estimate: self.estimate,
+            ..Default::default()
+        };
+        let id = db.add_task(&task)?;
+        for subtask in &self.subtasks {
+            subtask.add(db, Some((id, &task)), start)?;
+        }
+        Ok(id)
+    }
+}
+
+/// Load every `*.toml` in `dir`, sorted by name
+pub fn load(dir: Option<&Path>) -> Result<Vec<Template>, Box<dyn std::error::Error>> {
+    let mut templates = Vec::new();
+    let Some(dir) = dir.filter(|dir| dir.is_dir()) else {
+        return Ok(templates);
+    };
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let contents = fs::read_to_string(&path)?;
+        let task: TemplateTask =
+            toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        task.check()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        templates.push(Template {
+            name: name.to_string(),
+            task,
+        });
+    }
+    Ok(templates)
+}
+
+/// The template called `name`, or the only one whose name starts with it
+pub fn find<'a>(templates: &'a [Template], name: &str) -> Result<&'a Template, String> {
+    let name = name.to_lowercase();
+    if let Some(template) = templates.iter().find(|t| t.name.to_lowercase() == name) {
+        return Ok(template);
+    }
+    let matches: Vec<&Template> = templates
+        .iter()
+        .filter(|t| t.name.to_lowercase().starts_with(&name))
+        .collect();
+    match matches[..] {
+        [template] => Ok(template),
+        [] => Err(format!("no template '{}'", name)),
+        _ => Err(format!(
+            "'{}' could be {}",
+            name,
+            matches
+                .iter()
+                .map(|t| t.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Split "release +7d" into the template name and the date its offsets count
+/// from, which is `today` when left out
+pub fn parse_request(input: &str, today: NaiveDate) -> Result<(&str, NaiveDate), String> {
+    let input = input.trim();
+    if let Some((name, date)) = input.rsplit_once(char::is_whitespace) {
+        if let Some(start) = query::parse_date(date).and_then(|date| date.resolve(today)) {
+            return Ok((name.trim(), start));
+        }
+    }
+    if input.is_empty() {
+        return Err("enter a template name".to_string());
+    }
+    Ok((input, today))
+}
+
+/// Create the template's whole task tree in one transaction. Returns the
+/// top task's id and the number of tasks created.
+pub fn instantiate(
+    db: &Database,
+    template: &Template,
+    start: NaiveDate,
+) -> rusqlite::Result<(i32, usize)> {
+    db.transaction(|db| template.task.add(db, None, start))
+        .map(|id| (id, template.task.count()))
+}
+
+/// Parse a start date given on the command line
+pub fn parse_start(value: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    match query::parse_date(value) {
+        Some(DateValue::None) | None => Err(format!("'{}' is not a date", value)),
+        Some(date) => Ok(date.resolve(today).unwrap_or(today)),
+    }
+}
@@ -0,0 +1,366 @@
+//! Multi-line text input for task descriptions: cursor movement, word wrap,
+//! selection, paste, and handing the text to `$EDITOR`.
+
+use crate::theme::Theme;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    Frame,
+};
+use std::process::Command;
+
+#[derive(Default)]
+pub struct TextArea {
+    text: String,
+    /// Byte offset of the cursor in `text`
+    cursor: usize,
+    /// Other end of the selection while one is active
+    anchor: Option<usize>,
+    /// Column kept while moving up and down through shorter rows
+    goal_column: Option<usize>,
+    /// First wrapped row on screen
+    scroll: usize,
+    /// Last wrapped width, used for up/down movement between draws
+    width: usize,
+    /// Text copied or cut with Ctrl+C / Ctrl+X
+    clipboard: String,
+}
+
+impl TextArea {
+    pub fn new(text: &str) -> Self {
+        TextArea {
+            text: text.to_string(),
+            cursor: text.len(),
+            width: usize::MAX,
+            ..Default::default()
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the whole text, e.g. with what came back from `$EDITOR`
+    pub fn set_text(&mut self, text: &str) {
+        *self = TextArea {
+            clipboard: std::mem::take(&mut self.clipboard),
+            width: self.width,
+            ..TextArea::new(text)
+        };
+    }
+
+    fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor.filter(|anchor| *anchor != self.cursor)?;
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.text.replace_range(start..end, "");
+                self.cursor = start;
+                self.anchor = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.text.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+        self.anchor = None;
+        self.goal_column = None;
+    }
+
+    fn prev_boundary(&self, offset: usize) -> usize {
+        self.text[..offset]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, offset: usize) -> usize {
+        self.text[offset..]
+            .chars()
+            .next()
+            .map_or(offset, |c| offset + c.len_utf8())
+    }
+
+    /// Start of the word before `offset`, skipping whitespace first
+    fn prev_word(&self, offset: usize) -> usize {
+        let before = self.text[..offset].trim_end();
+        before.rfind(char::is_whitespace).map_or(0, |i| {
+            i + before[i..].chars().next().map_or(1, char::len_utf8)
+        })
+    }
+
+    /// End of the word after `offset`, skipping whitespace first
+    fn next_word(&self, offset: usize) -> usize {
+        let after = &self.text[offset..];
+        let skipped = after.len() - after.trim_start().len();
+        after[skipped..]
+            .find(char::is_whitespace)
+            .map_or(self.text.len(), |i| offset + skipped + i)
+    }
+
+    /// Byte ranges of the wrapped rows. Lines break after the last space that
+    /// fits, or mid-word when a word is wider than the area. A line that
+    /// exactly fills the width gets an empty row after it for the cursor.
+    fn rows(&self, width: usize) -> Vec<(usize, usize)> {
+        let width = width.max(1);
+        let mut rows = Vec::new();
+        let mut line_start = 0;
+        for line in self.text.split('\n') {
+            let end = line_start + line.len();
+            let mut start = line_start;
+            loop {
+                let fit = self.text[start..end]
+                    .char_indices()
+                    .nth(width)
+                    .map_or(end, |(i, _)| start + i);
+                if fit == end {
+                    rows.push((start, end));
+                    if self.text[start..end].chars().count() == width {
+                        rows.push((end, end));
+                    }
+                    break;
+                }
+                let brk = self.text[start..fit]
+                    .rfind(' ')
+                    .map_or(fit, |i| start + i + 1);
+                rows.push((start, brk));
+                start = brk;
+            }
+            line_start = end + 1;
+        }
+        rows
+    }
+
+    /// Row index and column of `offset` among `rows`
+    fn position(&self, rows: &[(usize, usize)], offset: usize) -> (usize, usize) {
+        let row = rows
+            .iter()
+            .rposition(|(start, _)| *start <= offset)
+            .unwrap_or(0);
+        (row, self.text[rows[row].0..offset].chars().count())
+    }
+
+    /// Last offset the cursor can take on a row. A soft-wrapped row ends
+    /// where the next one starts, so stop before its final character.
+    fn row_end(&self, rows: &[(usize, usize)], row: usize) -> usize {
+        let (start, end) = rows[row];
+        match rows.get(row + 1) {
+            Some((next, _)) if *next == end && end > start => self.prev_boundary(end),
+            _ => end,
+        }
+    }
+
+    fn move_vertically(&mut self, delta: isize) {
+        let rows = self.rows(self.width);
+        let (row, column) = self.position(&rows, self.cursor);
+        let column = *self.goal_column.get_or_insert(column);
+        let target = row as isize + delta;
+        if target < 0 {
+            self.cursor = 0;
+        } else if target as usize >= rows.len() {
+            self.cursor = self.text.len();
+        } else {
+            let target = target as usize;
+            let (start, _) = rows[target];
+            let end = self.row_end(&rows, target);
+            self.cursor = self.text[start..end]
+                .char_indices()
+                .nth(column)
+                .map_or(end, |(i, _)| start + i);
+        }
+    }
+
+    /// Put the cursor at a column and row of the area last drawn
+    pub fn click(&mut self, column: usize, row: usize) {
+        let rows = self.rows(self.width);
+        let row = (self.scroll + row).min(rows.len() - 1);
+        let (start, _) = rows[row];
+        let end = self.row_end(&rows, row);
+        self.cursor = self.text[start..end]
+            .char_indices()
+            .nth(column)
+            .map_or(end, |(i, _)| start + i);
+        self.anchor = None;
+        self.goal_column = None;
+    }
+
+    /// Scroll wheel: move the cursor up or down a row, scrolling with it
+    pub fn scroll(&mut self, delta: isize) {
+        self.anchor = None;
+        self.move_vertically(delta);
+    }
+
+    /// Handle an editing key. Returns false for keys the text area doesn't
+    /// use, so the caller can act on them.
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let before = self.cursor;
+        match key.code {
+            KeyCode::Char('a') if ctrl => {
+                self.anchor = Some(0);
+                self.cursor = self.text.len();
+                return true;
+            }
+            KeyCode::Char('c') | KeyCode::Char('x') if ctrl => {
+                if let Some((start, end)) = self.selection() {
+                    self.clipboard = self.text[start..end].to_string();
+                    if key.code == KeyCode::Char('x') {
+                        self.delete_selection();
+                    }
+                }
+                return true;
+            }
+            KeyCode::Char('v') if ctrl => {
+                let clipboard = self.clipboard.clone();
+                self.insert(&clipboard);
+                return true;
+            }
+            KeyCode::Char(_) if ctrl => return false,
+            KeyCode::Char(c) => {
+                self.insert(c.encode_utf8(&mut [0; 4]));
+                return true;
+            }
+            KeyCode::Enter => {
+                self.insert("\n");
+                return true;
+            }
+            KeyCode::Backspace => {
+                if !self.delete_selection() && self.cursor > 0 {
+                    let start = self.prev_boundary(self.cursor);
+                    self.text.replace_range(start..self.cursor, "");
+                    self.cursor = start;
+                }
+                self.goal_column = None;
+                return true;
+            }
+            KeyCode::Delete => {
+                if !self.delete_selection() {
+                    let end = self.next_boundary(self.cursor);
+                    self.text.replace_range(self.cursor..end, "");
+                }
+                self.goal_column = None;
+                return true;
+            }
+            KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::Home
+            | KeyCode::End => {}
+            _ => return false,
+        }
+
+        // Cursor movement; Shift extends the selection
+        if shift {
+            self.anchor.get_or_insert(before);
+        } else {
+            self.anchor = None;
+        }
+        if let KeyCode::Up | KeyCode::Down = key.code {
+            self.move_vertically(if key.code == KeyCode::Up { -1 } else { 1 });
+            return true;
+        }
+        self.goal_column = None;
+        let rows = self.rows(self.width);
+        let (row, _) = self.position(&rows, self.cursor);
+        self.cursor = match key.code {
+            KeyCode::Left if ctrl => self.prev_word(self.cursor),
+            KeyCode::Right if ctrl => self.next_word(self.cursor),
+            KeyCode::Left => self.prev_boundary(self.cursor),
+            KeyCode::Right => self.next_boundary(self.cursor),
+            KeyCode::Home if ctrl => 0,
+            KeyCode::End if ctrl => self.text.len(),
+            KeyCode::Home => rows[row].0,
+            _ => self.row_end(&rows, row),
+        };
+        true
+    }
+
+    /// Draw the wrapped text inside `block`, scrolled to keep the cursor in
+    /// view, and place the terminal cursor when focused
+    pub fn render(
+        &mut self,
+        f: &mut Frame,
+        area: Rect,
+        block: Block,
+        focused: bool,
+        theme: &Theme,
+    ) {
+        let inner = block.inner(area);
+        self.width = inner.width as usize;
+        let rows = self.rows(self.width);
+        let (row, column) = self.position(&rows, self.cursor);
+        let height = (inner.height as usize).max(1);
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + height {
+            self.scroll = row + 1 - height;
+        }
+
+        let selection = self.selection().filter(|_| focused);
+        let lines: Vec<Line> = rows
+            .iter()
+            .skip(self.scroll)
+            .take(height)
+            .map(|&(start, end)| {
+                let (from, to) = match selection {
+                    Some((sel_start, sel_end)) => {
+                        (sel_start.clamp(start, end), sel_end.clamp(start, end))
+                    }
+                    None => (end, end),
+                };
+                Line::from(vec![
+                    Span::styled(&self.text[start..from], theme.text()),
+                    Span::styled(&self.text[from..to], theme.selection()),
+                    Span::styled(&self.text[to..end], theme.text()),
+                ])
+            })
+            .collect();
+        f.render_widget(Paragraph::new(lines).block(block), area);
+        if focused {
+            f.set_cursor(
+                inner.x + column as u16,
+                inner.y + (row - self.scroll) as u16,
+            );
+        }
+    }
+}
+
+/// Let the user edit `text` in `$VISUAL` or `$EDITOR` (vi if neither is set)
+/// and return the result. The caller must leave raw mode first.
+pub fn edit_externally(text: &str) -> Result<String, String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or("$EDITOR is empty")?;
+    let path = std::env::temp_dir().join(format!("task-{}.md", uuid::Uuid::new_v4()));
+    std::fs::write(&path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let result = Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .map_err(|e| format!("can't run {}: {}", program, e))
+        .and_then(|status| {
+            if status.success() {
+                std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+            } else {
+                Err(format!("{} exited with {}", program, status))
+            }
+        });
+    let _ = std::fs::remove_file(&path);
+    // Editors add a final newline the description didn't have
+    result.map(|edited| edited.strip_suffix('\n').unwrap_or(&edited).to_string())
+}
@@ -0,0 +1,194 @@
+use crate::workflow::Workflow;
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Colours used across the task UI. User themes are TOML files in
+/// `<config dir>/themes/<name>.toml`; any colour left out falls back to the
+/// dark theme. Colours are names ("light-red"), hex ("#ff8800") or indexes ("42").
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    #[serde(skip)]
+    pub name: String,
+    pub text: Color,
+    pub border: Color,
+    pub border_focused: Color,
+    pub selection_fg: Color,
+    pub selection_bg: Color,
+    pub priority_high: Color,
+    pub priority_medium: Color,
+    pub priority_low: Color,
+    pub overdue: Color,
+    pub status_todo: Color,
+    pub status_in_progress: Color,
+    pub status_completed: Color,
+    pub badge_text: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            name: "dark".to_string(),
+            text: Color::White,
+            border: Color::DarkGray,
+            border_focused: Color::Cyan,
+            selection_fg: Color::Black,
+            selection_bg: Color::Cyan,
+            priority_high: Color::LightRed,
+            priority_medium: Color::Yellow,
+            priority_low: Color::Green,
+            overdue: Color::Red,
+            status_todo: Color::Blue,
+            status_in_progress: Color::Magenta,
+            status_completed: Color::Green,
+            badge_text: Color::Black,
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            name: "light".to_string(),
+            text: Color::Black,
+            border: Color::Gray,
+            border_focused: Color::Blue,
+            selection_fg: Color::White,
+            selection_bg: Color::Blue,
+            priority_high: Color::Red,
+            priority_medium: Color::Rgb(184, 134, 11),
+            priority_low: Color::Rgb(34, 139, 34),
+            overdue: Color::Red,
+            status_todo: Color::Rgb(70, 130, 180),
+            status_in_progress: Color::Rgb(148, 0, 211),
+            status_completed: Color::Rgb(34, 139, 34),
+            badge_text: Color::White,
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Theme {
+            name: "high-contrast".to_string(),
+            text: Color::White,
+            border: Color::White,
+            border_focused: Color::LightYellow,
+            selection_fg: Color::Black,
+            selection_bg: Color::LightYellow,
+            priority_high: Color::LightRed,
+            priority_medium: Color::LightYellow,
+            priority_low: Color::LightGreen,
+            overdue: Color::LightRed,
+            status_todo: Color::White,
+            status_in_progress: Color::LightCyan,
+            status_completed: Color::LightGreen,
+            badge_text: Color::Black,
+        }
+    }
+
+    pub fn text(&self) -> Style {
+        Style::default().fg(self.text)
+    }
+
+    pub fn border(&self, focused: bool) -> Style {
+        Style::default().fg(if focused {
+            self.border_focused
+        } else {
+            self.border
+        })
+    }
+
+    pub fn selection(&self) -> Style {
+        Style::default()
+            .fg(self.selection_fg)
+            .bg(self.selection_bg)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn priority(&self, priority: &str) -> Style {
+        let color = match priority {
+            "High" => self.priority_high,
+            "Medium" => self.priority_medium,
+            "Low" => self.priority_low,
+            _ => self.text,
+        };
+        Style::default().fg(color)
+    }
+
+    pub fn overdue(&self) -> Style {
+        Style::default()
+            .fg(self.overdue)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    /// The status's own colour, else the theme's for done statuses, the
+    /// initial one and those in between
+    pub fn status_badge(&self, status: &str, workflow: &Workflow) -> Style {
+        let color = match workflow.get(status) {
+            Some(status) if status.color.is_some() => status.color.unwrap_or(self.text),
+            Some(status) if status.done => self.status_completed,
+            Some(status) if status.name != workflow.initial() => self.status_in_progress,
+            _ => self.status_todo,
+        };
+        Style::default().fg(self.badge_text).bg(color)
+    }
+}
+
+/// Built-in themes followed by the user's, with one of them active
+pub struct Themes {
+    themes: Vec<Theme>,
+    current: usize,
+}
+
+impl Themes {
+    /// Load user themes from `dir` and select `selected` (or the first theme
+    /// when it is unknown). A user theme named like a built-in replaces it.
+    pub fn load(
+        dir: Option<&Path>,
+        selected: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut themes = vec![Theme::dark(), Theme::light(), Theme::high_contrast()];
+
+        if let Some(dir) = dir.filter(|dir| dir.is_dir()) {
+            let mut paths: Vec<_> = fs::read_dir(dir)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .collect();
+            paths.sort();
+            for path in paths {
+                let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                };
+                let contents = fs::read_to_string(&path)?;
+                let mut theme: Theme =
+                    toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+                theme.name = name;
+                match themes.iter().position(|t| t.name == theme.name) {
+                    Some(i) => themes[i] = theme,
+                    None => themes.push(theme),
+                }
+            }
+        }
+
+        let current = selected
+            .and_then(|name| themes.iter().position(|t| t.name == name))
+            .unwrap_or(0);
+        Ok(Themes { themes, current })
+    }
+
+    pub fn current(&self) -> &Theme {
+        &self.themes[self.current]
+    }
+
+    /// Switch to the next theme, wrapping around
+    pub fn next(&mut self) -> &Theme {
+        self.current = (self.current + 1) % self.themes.len();
+        self.current()
+    }
+}
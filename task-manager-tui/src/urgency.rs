@@ -0,0 +1,227 @@
+//! Urgency score used to order the task list: a weighted sum of priority,
+//! due date proximity, age, being blocked by unfinished sub-tasks or
+//! dependencies, and sub-task progress. The weights come from `[urgency]` in
+//! config.toml.
+
+use crate::config::UrgencyCoefficients;
+use crate::db::Task;
+use crate::workflow::Workflow;
+use chrono::{DateTime, NaiveDate};
+use std::collections::{HashMap, HashSet};
+
+/// 0.2 from two weeks out, rising linearly to 1.0 a week past due
+fn due_factor(task: &Task, today: NaiveDate) -> f64 {
+    let due = match task
+        .due_date
+        .as_deref()
+        .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok())
+    {
+        Some(due) => due,
+        None => return 0.0,
+    };
+    let days_overdue = (today - due).num_days() as f64;
+    if days_overdue >= 7.0 {
+        1.0
+    } else if days_overdue >= -14.0 {
+        (days_overdue + 14.0) * 0.8 / 21.0 + 0.2
+    } else {
+        0.2
+    }
+}
+
+fn age_factor(task: &Task, today: NaiveDate, max_days: f64) -> f64 {
+    let created = match DateTime::parse_from_rfc3339(&task.created_at) {
+        Ok(created) => created.date_naive(),
+        Err(_) => return 0.0,
+    };
+    if max_days <= 0.0 {
+        return 1.0;
+    }
+    ((today - created).num_days() as f64 / max_days).clamp(0.0, 1.0)
+}
+
+/// Urgency of one task given its (done, total) sub-task counts and how many
+/// of the tasks it depends on are unfinished. Done tasks score 0.
+pub fn urgency(
+    task: &Task,
+    subtasks: (usize, usize),
+    open_dependencies: usize,
+    coefficients: &UrgencyCoefficients,
+    workflow: &Workflow,
+    today: NaiveDate,
+) -> f64 {
+    if workflow.is_done(&task.status) {
+        return 0.0;
+    }
+    let priority = match task.priority.as_str() {
+        "High" => coefficients.priority_high,
+        "Medium" => coefficients.priority_medium,
+        "Low" => coefficients.priority_low,
+        _ => 0.0,
+    };
+    let (done, total) = subtasks;
+    let progress = if total == 0 {
+        0.0
+    } else {
+        done as f64 / total as f64
+    };
+    let blocked = if done < total || open_dependencies > 0 {
+        1.0
+    } else {
+        0.0
+    };
+    priority
+        + coefficients.due * due_factor(task, today)
+        + coefficients.age * age_factor(task, today, coefficients.age_max_days)
+        + coefficients.blocked * blocked
+        + coefficients.subtasks * progress
+}
+
+/// Urgency of every task, by id. `dependencies` has the ids of the tasks
+/// each task depends on; trashed ones no longer block, and neither do
+/// trashed or archived sub-tasks.
+pub fn scores(
+    tasks: &[Task],
+    dependencies: &HashMap<i32, Vec<i32>>,
+    coefficients: &UrgencyCoefficients,
+    workflow: &Workflow,
+    today: NaiveDate,
+) -> HashMap<i32, f64> {
+    let mut subtasks: HashMap<i32, (usize, usize)> = HashMap::new();
+    for task in tasks {
+        if task.is_hidden() {
+            continue;
+        }
+        if let Some(parent) = task.parent_task_id {
+            let counts = subtasks.entry(parent).or_default();
+            counts.1 += 1;
+            if workflow.is_done(&task.status) {
+                counts.0 += 1;
+            }
+        }
+    }
+    let open: HashSet<i32> = tasks
+        .iter()
+        .filter(|task| !workflow.is_done(&task.status) && task.trashed_at.is_none())
+        .map(|task| task.id)
+        .collect();
+    tasks
+        .iter()
+        .map(|task| {
+            let counts = subtasks.get(&task.id).copied().unwrap_or_default();
+            let open_dependencies = dependencies
+                .get(&task.id)
+                .into_iter()
+                .flatten()
+                .filter(|id| open.contains(id))
+                .count();
+            (
+                task.id,
+                urgency(
+                    task,
+                    counts,
+                    open_dependencies,
+                    coefficients,
+                    workflow,
+                    today,
+                ),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i32, parent: Option<i32>, status: &str) -> Task {
+        Task {
+            id,
+            parent_task_id: parent,
+            status: status.to_string(),
+            priority: "Medium".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn scores(tasks: &[Task], dependencies: &[(i32, i32)]) -> HashMap<i32, f64> {
+        let mut map: HashMap<i32, Vec<i32>> = HashMap::new();
+        for &(id, on) in dependencies {
+            map.entry(id).or_default().push(on);
+        }
+        super::scores(
+            tasks,
+            &map,
+            &UrgencyCoefficients::default(),
+            &Workflow::default(),
+            NaiveDate::from_ymd_opt(2026, 10, 14).unwrap(),
+        )
+    }
+
+    #[test]
+    fn open_dependencies_block() {
+        let coefficients = UrgencyCoefficients::default();
+        let trashed = Task {
+            trashed_at: Some("2026-10-01T00:00:00.000Z".to_string()),
+            ..task(4, None, "To Do")
+        };
+        let tasks = [
+            task(1, None, "To Do"),
+            task(2, None, "To Do"),
+            task(3, None, "Completed"),
+            trashed,
+            task(5, None, "To Do"),
+        ];
+        let scores = scores(&tasks, &[(1, 2), (5, 3), (5, 4)]);
+        let free = coefficients.priority_medium;
+        assert_eq!(scores[&1], free + coefficients.blocked);
+        assert_eq!(scores[&2], free);
+        assert_eq!(scores[&3], 0.0);
+        assert_eq!(
+            scores[&5], free,
+            "done and trashed dependencies don't block"
+        );
+    }
+
+    #[test]
+    fn unfinished_sub_tasks_block() {
+        let coefficients = UrgencyCoefficients::default();
+        let tasks = [
+            task(1, None, "To Do"),
+            task(2, Some(1), "Completed"),
+            task(3, Some(1), "To Do"),
+            task(4, None, "To Do"),
+            task(5, Some(4), "Completed"),
+        ];
+        let scores = scores(&tasks, &[]);
+        let free = coefficients.priority_medium;
+        assert_eq!(
+            scores[&1],
+            free + coefficients.blocked + coefficients.subtasks * 0.5
+        );
+        assert_eq!(scores[&4], free + coefficients.subtasks);
+    }
+
+    #[test]
+    fn hidden_sub_tasks_dont_block() {
+        let coefficients = UrgencyCoefficients::default();
+        let trashed = Task {
+            trashed_at: Some("2026-10-01T00:00:00.000Z".to_string()),
+            ..task(2, Some(1), "To Do")
+        };
+        let archived = Task {
+            archived_at: Some("2026-10-01T00:00:00.000Z".to_string()),
+            ..task(4, Some(3), "To Do")
+        };
+        let tasks = [
+            task(1, None, "To Do"),
+            trashed,
+            task(3, None, "To Do"),
+            archived,
+        ];
+        let scores = scores(&tasks, &[]);
+        let free = coefficients.priority_medium;
+        assert_eq!(scores[&1], free);
+        assert_eq!(scores[&3], free);
+    }
+}
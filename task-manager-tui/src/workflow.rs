@@ -0,0 +1,276 @@
+//! The statuses a task moves through, from `[[statuses]]` in config.toml.
+//! Each has an optional colour, whether it counts as done, and optionally
+//! the only statuses a task may reach it from.
+
+use ratatui::style::Color;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Status {
+    pub name: String,
+    /// Badge colour; the theme's status colours when left out
+    pub color: Option<Color>,
+    /// Done tasks aren't overdue, don't count towards plans and urgency, and
+    /// get archived
+    #[serde(default)]
+    pub done: bool,
+    /// Statuses a task has to be in to move to this one, e.g. `["Review"]`
+    /// before "Completed"; any status when left out
+    pub from: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Workflow {
+    statuses: Vec<Status>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        let status = |name: &str, done: bool| Status {
+            name: name.to_string(),
+            color: None,
+            done,
+            from: None,
+        };
+        Workflow {
+            statuses: vec![
+                status("To Do", false),
+                status("In Progress", false),
+                status("Completed", true),
+            ],
+        }
+    }
+}
+
+impl Workflow {
+    /// The statuses in order; none at all means the default workflow
+    pub fn new(statuses: Vec<Status>) -> Result<Self, String> {
+        if statuses.is_empty() {
+            return Ok(Workflow::default());
+        }
+        for (i, status) in statuses.iter().enumerate() {
+            if status.name.trim().is_empty() {
+                return Err("a status has no name".to_string());
+            }
+            if statuses[..i].iter().any(|s| s.name == status.name) {
+                return Err(format!("'{}' is listed twice", status.name));
+            }
+            for from in status.from.iter().flatten() {
+                if !statuses.iter().any(|s| s.name == *from) {
+                    return Err(format!(
+                        "'{}' can be reached from '{}', which isn't a status",
+                        status.name, from
+                    ));
+                }
+            }
+        }
+        if statuses[0].done {
+            return Err(format!(
+                "new tasks start as '{}', so it can't be done",
+                statuses[0].name
+            ));
+        }
+        if !statuses.iter().any(|status| status.done) {
+            return Err("no status has `done = true`".to_string());
+        }
+        Ok(Workflow { statuses })
+    }
+
+    pub fn statuses(&self) -> &[Status] {
+        &self.statuses
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.statuses.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Status> {
+        self.statuses.iter().find(|status| status.name == name)
+    }
+
+    /// Position in the workflow, with unknown statuses last
+    pub fn rank(&self, name: &str) -> usize {
+        self.statuses
+            .iter()
+            .position(|status| status.name == name)
+            .unwrap_or(self.statuses.len())
+    }
+
+    pub fn is_done(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|status| status.done)
+    }
+
+    /// The status new tasks get
+    pub fn initial(&self) -> &str {
+        &self.statuses[0].name
+    }
+
+    /// The first one not done after the initial status, like "In Progress"
+    pub fn started(&self) -> &str {
+        self.statuses[1..]
+            .iter()
+            .find(|status| !status.done)
+            .map_or(self.initial(), |status| &status.name)
+    }
+
+    /// The status the complete action sets
+    pub fn completed(&self) -> &str {
+        self.statuses
+            .iter()
+            .find(|status| status.done)
+            .map_or(self.initial(), |status| &status.name)
+    }
+
+    /// The status `value` names, ignoring case and spaces
+    pub fn choose(&self, value: &str) -> Option<&str> {
+        let normalize = |s: &str| s.to_lowercase().replace(' ', "");
+        self.statuses
+            .iter()
+            .find(|status| normalize(&status.name) == normalize(value))
+            .map(|status| status.name.as_str())
+    }
+
+    /// Whether a task may move from status `from` to `to`
+    pub fn check(&self, from: &str, to: &str) -> Result<(), String> {
+        if from == to {
+            return Ok(());
+        }
+        let Some(status) = self.get(to) else {
+            return Err(format!(
+                "unknown status '{}' ({})",
+                to,
+                self.names().join(", ")
+            ));
+        };
+        match &status.from {
+            Some(from_any) if !from_any.iter().any(|name| name == from) => Err(format!(
+                "'{}' can only be reached from {}",
+                to,
+                from_any.join(" or ")
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(name: &str, done: bool, from: Option<&[&str]>) -> Status {
+        Status {
+            name: name.to_string(),
+            color: None,
+            done,
+            from: from.map(|from| from.iter().map(|s| s.to_string()).collect()),
+        }
+    }
+
+    fn review() -> Workflow {
+        Workflow::new(vec![
+            status("Backlog", false, None),
+            status("Doing", false, None),
+            status("Review", false, Some(&["Doing"])),
+            status("Done", true, Some(&["Review"])),
+            status("Dropped", true, None),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn defaults_when_empty() {
+        let workflow = Workflow::new(Vec::new()).unwrap();
+        assert_eq!(workflow.names(), ["To Do", "In Progress", "Completed"]);
+        assert_eq!(workflow.initial(), "To Do");
+        assert_eq!(workflow.started(), "In Progress");
+        assert_eq!(workflow.completed(), "Completed");
+        assert!(workflow.check("To Do", "Completed").is_ok());
+    }
+
+    #[test]
+    fn names_roles_and_order() {
+        let workflow = review();
+        assert_eq!(workflow.initial(), "Backlog");
+        assert_eq!(workflow.started(), "Doing");
+        assert_eq!(workflow.completed(), "Done");
+        assert!(workflow.is_done("Dropped"));
+        assert!(!workflow.is_done("Review"));
+        assert!(!workflow.is_done("Unknown"));
+        assert_eq!(workflow.rank("Review"), 2);
+        assert_eq!(workflow.rank("Unknown"), 5);
+        assert_eq!(workflow.choose("dro pped"), Some("Dropped"));
+        assert_eq!(workflow.choose("later"), None);
+    }
+
+    #[test]
+    fn checks_transitions() {
+        let workflow = review();
+        assert!(workflow.check("Doing", "Review").is_ok());
+        assert!(workflow.check("Review", "Done").is_ok());
+        assert!(workflow.check("Backlog", "Dropped").is_ok());
+        assert!(workflow.check("Done", "Backlog").is_ok());
+        assert!(workflow.check("Done", "Done").is_ok());
+        assert_eq!(
+            workflow.check("Backlog", "Done"),
+            Err("'Done' can only be reached from Review".to_string())
+        );
+        assert_eq!(
+            workflow.check("Backlog", "Later"),
+            Err("unknown status 'Later' (Backlog, Doing, Review, Done, Dropped)".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_workflows() {
+        let error = |statuses| Workflow::new(statuses).unwrap_err();
+        assert_eq!(
+            error(vec![status(" ", false, None), status("Done", true, None)]),
+            "a status has no name"
+        );
+        assert_eq!(
+            error(vec![status("A", false, None), status("A", true, None)]),
+            "'A' is listed twice"
+        );
+        assert_eq!(
+            error(vec![
+                status("A", false, None),
+                status("B", true, Some(&["C"]))
+            ]),
+            "'B' can be reached from 'C', which isn't a status"
+        );
+        assert_eq!(
+            error(vec![status("A", true, None), status("B", false, None)]),
+            "new tasks start as 'A', so it can't be done"
+        );
+        assert_eq!(
+            error(vec![status("A", false, None), status("B", false, None)]),
+            "no status has `done = true`"
+        );
+    }
+
+    #[test]
+    fn reads_statuses_from_toml() {
+        #[derive(Deserialize)]
+        struct Config {
+            statuses: Vec<Status>,
+        }
+        let config: Config = toml::from_str(
+            r##"
+            [[statuses]]
+            name = "Open"
+            [[statuses]]
+            name = "Closed"
+            color = "#ff0000"
+            done = true
+            from = ["Open"]
+            "##,
+        )
+        .unwrap();
+        let workflow = Workflow::new(config.statuses).unwrap();
+        let closed = workflow.get("Closed").unwrap();
+        assert_eq!(closed.color, Some(Color::Rgb(255, 0, 0)));
+        assert!(closed.done);
+        assert!(toml::from_str::<Config>("[[statuses]]\nname = \"A\"\ncolour = \"red\"").is_err());
+    }
+}